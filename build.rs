@@ -0,0 +1,28 @@
+//! Records build metadata for `todo version`.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let run = |program: &str, args: &[&str]| {
+        Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    // Building from a source tarball without git is fine; say so instead.
+    let commit = run("git", &["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = run(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+
+    println!("cargo:rustc-env=TODOSTER_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=TODOSTER_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=TODOSTER_TARGET={}", target);
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
@@ -1,22 +1,96 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, Local};
-use clap::{Parser, Subcommand};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use notify::{EventKind, RecursiveMode, Watcher};
 use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::ops::{Index, IndexMut};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration as StdDuration, Instant};
+
+#[cfg(feature = "tui")]
+mod tui;
 
 /// Simple RON-based to-do app.
 #[derive(Parser)]
 #[command(name = "todo")]
-#[command(about = "RON-backed todo CLI", long_about = None)]
+#[command(version, about = "RON-backed todo CLI", long_about = None)]
 pub struct Cli {
-    /// Path to the RON storage file (default: ~/.config/todoster/todos.ron)
+    /// Path to the RON storage file (default: ~/.local/share/todoster/todos.ron)
     #[arg(short, long)]
     pub file: Option<PathBuf>,
 
+    /// Print diagnostics (storage path, item counts, timing) to stderr
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Commit the data file to git after each change (skipped outside a repo)
+    #[arg(long)]
+    pub git: bool,
+
+    /// Disable ANSI colors and highlighting (also honoured: NO_COLOR env var)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Print plain ASCII only: "--" headings, no emoji, other characters as "?"
+    #[arg(long, visible_alias = "plain")]
+    pub ascii: bool,
+
+    /// Show dates as RFC 3339 timestamps instead of "in 3d" / "2 hrs" countdowns
+    #[arg(long, visible_alias = "plain-date")]
+    pub iso_dates: bool,
+
+    /// First day of the week (overrides `week_start` in config.ron)
+    #[arg(long, value_enum)]
+    pub week_start: Option<WeekStart>,
+
+    /// How to show time until a repeat (overrides `repeat_display` in config.ron)
+    #[arg(long, value_enum)]
+    pub repeat_display: Option<RepeatDisplay>,
+
+    /// Don't ask before bulk operations over the configured threshold
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Don't count repeats missed while a task sat completed ("missed N")
+    #[arg(long)]
+    pub no_catchup: bool,
+
+    /// Show several list files as one read-only `list`, e.g. "work.ron,home.ron"
+    #[arg(long, value_delimiter = ',', conflicts_with = "file")]
+    pub merge_files: Vec<PathBuf>,
+
+    /// Use this configured theme instead of the one for the current list
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// Encrypt the data file, asking for the passphrase unless TODOSTER_KEY is set
+    #[arg(long)]
+    pub encrypt: bool,
+
+    /// Save the data file on one line instead of pretty-printed (also `compact` in config.ron)
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Leave out optional extras, such as the `show_summary_on_exit` line (confirmations still need `--yes`)
+    #[arg(short, long)]
+    pub quiet: bool,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -24,360 +98,5560 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// List all tasks (incomplete first, then complete)
-    List,
+    List {
+        /// Also show tasks whose start date is still in the future
+        #[arg(long)]
+        show_deferred: bool,
+
+        /// Send a desktop notification for each repeating task due today or overdue
+        #[arg(long)]
+        notify: bool,
+
+        /// Print the tasks as JSON instead of the human-readable list
+        #[arg(long)]
+        json: bool,
+
+        /// Show completed tasks in storage order instead of most recent first
+        #[arg(long, visible_alias = "reverse-complete")]
+        insertion_order: bool,
+
+        /// Split the list by completion status (default) or by tag
+        #[arg(long, value_enum, default_value_t = GroupBy::Status)]
+        group_by: GroupBy,
+
+        /// Order incomplete tasks by this instead of storage order
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+
+        /// Reverse the `--sort` order
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+
+        /// With `--sort due`, place repeats with no deadline by when they are next due
+        #[arg(long, requires = "sort")]
+        with_repeats: bool,
+
+        /// Only show tasks added since `list` was last run
+        #[arg(long, conflicts_with = "json")]
+        new: bool,
+
+        /// Fields to show for each task, in this order, e.g. "index,text,due"
+        #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "json")]
+        columns: Vec<Column>,
+
+        /// One list in storage order, each task marked [x] or [ ], no sections
+        #[arg(long, conflicts_with_all = ["json", "group_by", "sort"])]
+        flat: bool,
+
+        /// Also show tasks still in the inbox (see `inbox`)
+        #[arg(long)]
+        show_inbox: bool,
+
+        /// Leave out the priority legend under the list
+        #[arg(long, conflicts_with = "json")]
+        no_legend: bool,
+
+        /// Only incomplete tasks due by the end of this day (e.g. "friday", "+1w", "2026-02-01")
+        #[arg(long, conflicts_with = "json")]
+        due_before: Option<String>,
+
+        /// Wrap task lines to this many columns (0: don't wrap; default: the terminal's width)
+        #[arg(long, conflicts_with = "json")]
+        wrap: Option<usize>,
+
+        /// End each task with a NUL byte instead of a newline and print nothing else (for `xargs -0`)
+        #[arg(long, visible_alias = "null", conflicts_with_all = ["json", "wrap"])]
+        print0: bool,
+    },
 
     /// Add a new task
     Add {
         /// The task text
+        #[arg(required_unless_present = "from_clipboard")]
+        text: Option<String>,
+        /// Use the clipboard's text instead (needs the `clipboard` feature)
+        #[arg(long)]
+        from_clipboard: bool,
+        /// Capture into the inbox, out of `list` until you `triage` it
+        #[arg(long)]
+        inbox: bool,
+        /// Repeat interval in days, hours with an "h" suffix (e.g. "4h"), or
+        /// daily / weekly / fortnightly / monthly / weekday
+        #[arg(short, long, allow_negative_numbers = true)]
+        repeat: Option<RepeatInterval>,
+        /// Repeat on days of the week instead, e.g. "mon,thu"
+        #[arg(long, conflicts_with = "repeat")]
+        repeat_on: Option<String>,
+        /// Stop repeating after this date (e.g. "+2w", "2026-02-01")
+        #[arg(long)]
+        repeat_until: Option<String>,
+        /// Stop repeating after this many more repeats
+        #[arg(long)]
+        repeat_count: Option<u32>,
+        /// Time of day the repeat falls due, e.g. "08:00"
+        #[arg(long)]
+        time: Option<String>,
+        /// Move a repeat that lands on a weekend to the Monday after
+        #[arg(long)]
+        skip_weekends: bool,
+        /// Hide the task until this date (e.g. "tomorrow", "+3d", "2026-02-01")
+        #[arg(long, visible_alias = "defer")]
+        start: Option<String>,
+        /// Deadline (e.g. "friday", "+1w", "2026-02-01")
+        #[arg(long)]
+        due: Option<String>,
+        /// Have `remind` list the task this long before its deadline (e.g. "30m", "2h", "1d")
+        #[arg(long)]
+        remind_before: Option<String>,
+        /// Keep "!high", "#tag" and "due:<date>" in the text instead of
+        /// reading them as priority, tags and deadline
+        #[arg(long)]
+        no_parse: bool,
+        /// Count towards a target instead (see `progress`), e.g. 10 pages
+        #[arg(long)]
+        target: Option<u32>,
+        /// Expected time in minutes (log actual time with `track`)
+        #[arg(long)]
+        estimate: Option<u32>,
+        /// How important the task is
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
+        /// Insert right after this task instead of at the end (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(long, conflicts_with = "before", allow_negative_numbers = true)]
+        after: Option<TaskRef>,
+        /// Insert right before this task instead of at the end (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(long, allow_negative_numbers = true)]
+        before: Option<TaskRef>,
+        /// Tag to attach (repeat for several)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Free-form notes for the task
+        #[arg(long)]
+        notes: Option<String>,
+        /// Link for the task (http:// or https://), opened with `open`
+        #[arg(long)]
+        url: Option<String>,
+        /// Shorten text over `max_text_length` instead of refusing it
+        #[arg(long)]
+        truncate: bool,
+    },
+
+    /// Save (or overwrite) a named template for tasks you add often
+    TemplateSave {
+        /// Template name, used with `add-from-template`
+        name: String,
+        /// Text of the tasks it creates
         text: String,
-        /// Repeat interval in days
-        #[arg(short, long)]
-        repeat: Option<i64>,
+        /// Repeat interval in days, hours with an "h" suffix (e.g. "4h"), or
+        /// daily / weekly / fortnightly / monthly / weekday
+        #[arg(short, long, allow_negative_numbers = true)]
+        repeat: Option<RepeatInterval>,
+        /// How important the tasks are
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
+        /// Tag to attach (repeat for several)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// Add a task from a saved template
+    AddFromTemplate {
+        /// Template name (see `templates`)
+        name: String,
     },
 
-    /// Mark a task as complete by index (as shown in `list`)
+    /// List saved templates
+    Templates,
+
+    /// Mark tasks as complete by index (as shown in `list`)
     Complete {
-        /// Index of the task to complete
-        index: usize,
+        /// Indexes/ranges to complete, e.g. "0,2,5-7" or "0 2 5-7" (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true, required_unless_present = "overdue")]
+        indexes: Vec<String>,
+        /// Complete every repeating task that is due or overdue instead
+        #[arg(long, conflicts_with = "indexes")]
+        overdue: bool,
+        /// Short note about how it went, kept until the task next resets
+        #[arg(long)]
+        note: Option<String>,
+        /// Show when each task would next be due, without completing anything
+        #[arg(long, visible_alias = "preview")]
+        dry_run: bool,
+    },
+
+    /// Flip tasks between complete and incomplete
+    Toggle {
+        /// Comma-separated list of indexes/ranges, e.g. "0,2,5-7" (`-1` is the last task, `#id` a task by id)
+        #[arg(allow_negative_numbers = true)]
+        indexes: String,
+    },
+
+    /// Skip one occurrence of a repeating task without completing it
+    Skip {
+        /// Index of the repeating task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+    },
+
+    /// Lengthen or shorten a task's repeat interval by some days
+    Bump {
+        /// Index of the repeating task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+        /// Days to add to the interval (negative to shorten; stops at 1)
+        #[arg(allow_negative_numbers = true)]
+        delta: i64,
+    },
+
+    /// Record progress on a task with a target; completes it once reached
+    Progress {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+        /// How much to add
+        #[arg(default_value_t = 1)]
+        amount: u32,
+    },
+
+    /// Log time spent on a task without completing it
+    Track {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+        /// Minutes to add to the time spent
+        #[arg(long)]
+        spent: u32,
+    },
+
+    /// Pin a task so it's listed above the others while incomplete
+    Pin {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+    },
+
+    /// Unpin a task
+    Unpin {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+    },
+
+    /// Show only this task in `list` until `unfocus`
+    Focus {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+    },
+
+    /// Leave focus mode and list every task again
+    Unfocus,
+
+    /// Open a task's link in the default browser
+    Open {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
     },
 
     /// Mark a task as incomplete again
     Undo {
-        /// Index of the task to mark incomplete
-        index: usize,
+        /// Index or comma-separated indexes/ranges to mark incomplete, e.g. "1-3" (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        indexes: String,
     },
 
     /// Edit an existing task
     Edit {
-        /// Index of the task to edit
-        index: usize,
+        /// Index of the task to edit (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
 
         /// New text for the task
         #[arg(long)]
         text: Option<String>,
 
-        /// New repeat interval in days
-        #[arg(long)]
-        repeat: Option<i64>,
+        /// Shorten text over `max_text_length` instead of refusing it
+        #[arg(long, requires = "text")]
+        truncate: bool,
+
+        /// New repeat interval in days, hours with an "h" suffix (e.g. "4h"),
+        /// or daily / weekly / fortnightly / monthly / weekday
+        #[arg(long, allow_negative_numbers = true)]
+        repeat: Option<RepeatInterval>,
+
+        /// Repeat on days of the week instead, e.g. "mon,thu"
+        #[arg(long, conflicts_with = "repeat")]
+        repeat_on: Option<String>,
 
-        /// Clear the repeat interval
+        /// Clear the repeat interval (and any repeat limits)
         #[arg(long)]
         clear_repeat: bool,
-    },
 
-    /// Delete one or more tasks (comma-separated indexes and ranges)
-    Delete {
-        /// Comma-separated list of indexes/ranges, e.g. "0,2,5-7"
-        indexes: String,
+        /// Stop repeating after this date (e.g. "+2w", "2026-02-01")
+        #[arg(long)]
+        repeat_until: Option<String>,
 
-        /// Actually perform deletion (otherwise just show what would be deleted)
+        /// Stop repeating after this many more repeats
         #[arg(long)]
-        confirm: bool,
-    },
+        repeat_count: Option<u32>,
 
-    /// Show a table of available commands
-    Commands,
-}
+        /// Time of day the repeat falls due, e.g. "08:00"
+        #[arg(long)]
+        time: Option<String>,
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TodoItem {
-    pub text: String,
-    pub complete: bool,
-    pub complete_date: Option<DateTime<Local>>,
-    pub repeat_days: Option<i64>,
-}
+        /// Clear the due time (back to the default)
+        #[arg(long, conflicts_with = "time")]
+        clear_time: bool,
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct TodoList {
-    pub items: Vec<TodoItem>,
-}
+        /// Move a repeat that lands on a weekend to the Monday after
+        #[arg(long)]
+        skip_weekends: bool,
 
-impl TodoItem {
-    pub fn new(text: String, repeat_days: Option<i64>) -> Self {
-        Self {
-            text,
-            complete: false,
-            complete_date: None,
-            repeat_days,
-        }
-    }
+        /// Let the repeat fall on weekends again
+        #[arg(long, conflicts_with = "skip_weekends")]
+        allow_weekends: bool,
 
-    pub fn mark_complete(&mut self, now: DateTime<Local>) {
-        self.complete = true;
-        self.complete_date = Some(now);
-    }
+        /// New start date (e.g. "tomorrow", "+3d", "2026-02-01")
+        #[arg(long, visible_alias = "defer")]
+        start: Option<String>,
 
-    pub fn mark_incomplete(&mut self) {
-        self.complete = false;
-        self.complete_date = None;
-    }
+        /// Clear the start date so the task shows immediately
+        #[arg(long)]
+        clear_start: bool,
 
-    pub fn should_reset(&self, now: DateTime<Local>) -> bool {
-        if !self.complete {
-            return false;
-        }
+        /// New deadline (e.g. "friday", "+1w", "2026-02-01")
+        #[arg(long)]
+        due: Option<String>,
 
-        if let (Some(done_at), Some(days)) = (self.complete_date, self.repeat_days) {
-            let next_due = done_at + Duration::days(days);
-            now >= next_due
-        } else {
-            false
-        }
-    }
+        /// Remove the deadline
+        #[arg(long)]
+        clear_due: bool,
 
-    pub fn reset_if_due(&mut self, now: DateTime<Local>) {
-        if self.should_reset(now) {
-            self.complete = false;
-            self.complete_date = None;
-        }
-    }
+        /// Have `remind` list the task this long before its deadline (e.g. "30m", "2h", "1d")
+        #[arg(long)]
+        remind_before: Option<String>,
 
-    pub fn time_until_next_repeat(&self, now: DateTime<Local>) -> Option<Duration> {
-        if !self.complete {
-            return None;
-        }
-        if let (Some(done_at), Some(days)) = (self.complete_date, self.repeat_days) {
-            let next_due = done_at + Duration::days(days);
-            Some(next_due - now)
-        } else {
-            None
-        }
-    }
-}
+        /// Stop reminding ahead of the deadline
+        #[arg(long)]
+        clear_remind: bool,
 
-impl TodoList {
-    fn load(path: &PathBuf) -> Result<Self> {
-        if !path.exists() {
-            return Ok(TodoList::default());
-        }
+        /// New priority for the task
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
 
-        let contents = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        /// Remove the task's priority
+        #[arg(long)]
+        clear_priority: bool,
 
-        let list: TodoList =
-        ron::from_str(&contents).with_context(|| "Failed to parse RON data")?;
+        /// New notes for the task
+        #[arg(long)]
+        notes: Option<String>,
 
-        Ok(list)
-    }
+        /// Remove the task's notes
+        #[arg(long)]
+        clear_notes: bool,
 
-    fn save(&self, path: &PathBuf) -> Result<()> {
-        // Make sure the directory exists (for ~/.config/todoster/todos.ron)
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-        }
+        /// New link for the task (http:// or https://)
+        #[arg(long)]
+        url: Option<String>,
 
-        let pretty = PrettyConfig::new()
-        .separate_tuple_members(true)
-        .enumerate_arrays(true);
+        /// Remove the task's link
+        #[arg(long, conflicts_with = "url")]
+        clear_url: bool,
+    },
 
-        let ron_string =
-        ron::ser::to_string_pretty(self, pretty).with_context(|| "Failed to serialize RON")?;
+    /// Delete one or more tasks (by indexes/ranges, or by tag/status selectors)
+    Delete {
+        /// Indexes/ranges, e.g. "0,2,5-7" or "0 2 5-7" (`-1` is the last task, `#id` a task by id)
+        #[arg(allow_negative_numbers = true)]
+        indexes: Vec<String>,
 
-        let mut file = fs::File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
-        file.write_all(ron_string.as_bytes())
-        .with_context(|| "Failed to write RON data")?;
-        Ok(())
-    }
+        /// Select tasks carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
 
-    fn auto_reset_repeating(&mut self, now: DateTime<Local>) {
-        for item in &mut self.items {
-            item.reset_if_due(now);
-        }
-    }
+        /// Select completed tasks
+        #[arg(long)]
+        completed: bool,
 
-    fn add(&mut self, text: String, repeat_days: Option<i64>) {
-        self.items.push(TodoItem::new(text, repeat_days));
-    }
-}
+        /// Select incomplete tasks
+        #[arg(long)]
+        incomplete: bool,
 
-fn default_file_path() -> PathBuf {
-    let base = env::var("XDG_CONFIG_HOME")
-    .map(PathBuf::from)
-    .or_else(|_| {
-        env::var("HOME").map(|home| PathBuf::from(home).join(".config"))
-    })
-    .unwrap_or_else(|_| PathBuf::from("."));
+        /// Select every task in the list (asks you to type DELETE)
+        #[arg(long, conflicts_with_all = ["indexes", "tag", "completed", "incomplete"])]
+        all: bool,
 
-    base.join("todoster").join("todos.ron")
-}
+        /// Skip the typed confirmation for --all (for scripts)
+        #[arg(long)]
+        force: bool,
 
-fn print_list(list: &TodoList, now: DateTime<Local>) {
-    let mut incomplete: Vec<(usize, &TodoItem)> = Vec::new();
-    let mut complete: Vec<(usize, &TodoItem)> = Vec::new();
+        /// Actually perform deletion (otherwise just show what would be deleted)
+        #[arg(long)]
+        confirm: bool,
+    },
 
-    for (idx, item) in list.items.iter().enumerate() {
-        if item.complete {
-            complete.push((idx, item));
-        } else {
-            incomplete.push((idx, item));
-        }
-    }
+    /// Add and/or remove tags across several tasks at once
+    Tag {
+        /// Comma-separated list of indexes/ranges, e.g. "0,2,5-7" (`-1` is the last task, `#id` a task by id)
+        #[arg(allow_negative_numbers = true)]
+        indexes: String,
 
-    println!("=== Incomplete tasks ===");
-    if incomplete.is_empty() {
-        println!("(none)");
-    } else {
-        for (idx, item) in incomplete {
-            let repeat_info = match item.repeat_days {
-                Some(days) => format!("(Repeat: {} d)", days),
-                None => String::new(),
-            };
+        /// Tags to add
+        add: Vec<String>,
 
-            if repeat_info.is_empty() {
-                println!("[{}] {}", idx, item.text);
-            } else {
-                println!("[{}] {} {}", idx, item.text, repeat_info);
-            }
-        }
-    }
+        /// Tags to remove (repeat for several)
+        #[arg(long)]
+        remove: Vec<String>,
+    },
 
-    println!();
-    println!("=== Complete tasks ===");
-    if complete.is_empty() {
-        println!("(none)");
-    } else {
-        for (idx, item) in complete {
-            let repeat_info = match item.time_until_next_repeat(now) {
-                Some(diff) => {
-                    if diff.num_seconds() <= 0 {
-                        format!("(repeat: due now / overdue by {} days)", -diff.num_days())
-                    } else {
-                        let days = diff.num_days();
-                        let hours = (diff - Duration::days(days)).num_hours();
-                        if days > 0 {
-                            format!("(repeat in {}d, {}hrs)", days, hours)
-                        } else {
-                            format!("(repeat in {} hrs)", hours)
-                        }
-                    }
-                }
-                None => {
-                    if item.repeat_days.is_some() {
-                        "(repeat: no completion date yet)".to_string()
-                    } else {
-                        "(no repeat)".to_string()
-                    }
-                }
-            };
+    /// Show the tasks captured with `add --inbox`, waiting to be triaged
+    Inbox,
 
-            println!("[{}] {} {}", idx, item.text, repeat_info);
-        }
-    }
-}
+    /// Move a task out of the inbox, optionally organising it on the way
+    Triage {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
 
-fn print_command_table() {
-    println!("=== Todoster Commands ===\n");
+        /// Priority to give it
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
 
-    println!("{:<45} {}", "todo", "List tasks (default)");
-    println!("{:<45} {}", "todo list", "List tasks");
+        /// Tag to attach (repeat for several)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
 
-    println!("{:<45} {}", "todo add \"<text>\"", "Add a new task");
-    println!("{:<45} {}", "todo add \"<text>\" --repeat <days>", "Add repeating task");
+        /// Deadline (e.g. "friday", "+1w", "2026-02-01")
+        #[arg(long)]
+        due: Option<String>,
+    },
 
-    println!("{:<45} {}", "todo complete <index>", "Mark a task complete");
-    println!("{:<45} {}", "todo undo <index>", "Mark a task incomplete again");
+    /// Pick an incomplete task at random (not deferred or in the inbox)
+    Random {
+        /// Only pick from tasks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
 
-    println!("{:<45} {}", "todo edit <index> --text \"<new>\"", "Edit task text");
-    println!("{:<45} {}", "todo edit <index> --repeat <days>", "Change repeat interval");
-    println!("{:<45} {}", "todo edit <index> --clear-repeat", "Remove repeat interval");
+    /// Print one line per repeating task due today or overdue (and per task
+    /// in its reminder lead time), and nothing at all otherwise (for cron)
+    Remind,
 
-    println!("{:<45} {}", "todo delete <i1,i2,i3>", "Delete multiple tasks (by index)");
-    println!("{:<45} {}", "todo delete 1-4,7", "Supports ranges (inclusive)");
-    println!("{:<45} {}", "todo delete 0,2-3,7 --confirm", "Actually perform deletion");
-    println!("{:<45} {}", "todo delete 0,2-3,7", "Dry-run (shows what would be deleted)");
+    /// List saved snapshots of the list, newest first
+    History,
 
-    println!("{:<45} {}", "todo --file <path> <command>", "Use a custom RON file");
+    /// Show version and build details (for bug reports)
+    Version,
 
-    println!("\nIndexes are currently 0-based (first item = 0).");
-}
+    /// Check the data file for problems (read-only unless `--fix`); exits
+    /// nonzero if any are left
+    Doctor {
+        /// Repair what can be repaired (backing the file up first)
+        #[arg(long)]
+        fix: bool,
+    },
 
-// Make this public so tests (and main.rs) can use it.
-pub fn parse_index_list(spec: &str) -> Vec<usize> {
-    let mut result = Vec::new();
+    /// Open the data file in $EDITOR, then check it still loads
+    EditFile,
 
-    for part in spec.split(',') {
-        let p = part.trim();
-        if p.is_empty() {
-            continue;
-        }
+    /// Roll the list back to a snapshot from `history`
+    RestoreSnapshot {
+        /// Snapshot number as shown by `history` (1 = newest)
+        n: usize,
+    },
 
-        if let Some((start_s, end_s)) = p.split_once('-') {
-            let start = match start_s.trim().parse::<usize>() {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            let end = match end_s.trim().parse::<usize>() {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
+    /// Show counts for the whole list
+    Stats {
+        /// Print the numbers as a JSON object instead of a table
+        #[arg(long)]
+        json: bool,
+    },
 
-            if start <= end {
-                for i in start..=end {
-                    result.push(i);
-                }
-            } else {
-                for i in end..=start {
-                    result.push(i);
-                }
-            }
-        } else if let Ok(v) = p.parse::<usize>() {
-            result.push(v);
-        }
-    }
+    /// Show how consistently a repeating task has been completed
+    StatsTask {
+        /// Index of the task (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        index: TaskRef,
+    },
 
+    /// Merge duplicate tasks into one, then delete the duplicates
+    Merge {
+        /// Index of the task to keep (or `first` / `last` / `-N` from the end / `#id`)
+        #[arg(allow_negative_numbers = true)]
+        into: TaskRef,
+
+        /// Comma-separated list of indexes/ranges to merge in, e.g. "3,5-6"
+        #[arg(allow_negative_numbers = true)]
+        from: String,
+
+        /// Actually perform the merge (otherwise just show what would happen)
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Remove tasks with identical text, keeping the most complete copy
+    Dedupe {
+        /// Treat text differing only in letter case as identical
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Actually remove the duplicates (otherwise just show the groups)
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Replace text in every task that contains it
+    Replace {
+        /// Text to look for (case-sensitive unless --ignore-case)
+        from: String,
+
+        /// Text to put in its place
+        to: String,
+
+        /// Match `from` regardless of letter case
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Actually change the tasks (otherwise just show before/after)
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Find tasks whose text contains the query (case-insensitive)
+    Search {
+        /// Text to look for
+        query: String,
+
+        /// Show at most this many results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Print only how many tasks match (every match, ignoring --limit)
+        #[arg(long, conflicts_with = "limit")]
+        count: bool,
+
+        /// End each match with a NUL byte instead of a newline and print nothing else (for `xargs -0`)
+        #[arg(long, visible_alias = "null", conflicts_with = "count")]
+        print0: bool,
+    },
+
+    /// Write all tasks to stdout in a machine-readable format
+    Export {
+        /// Output format (default: from the --to extension, otherwise json)
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+
+        /// Add computed fields (overdue_days, age_days) to JSON output
+        #[arg(long)]
+        computed: bool,
+
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        to: Option<PathBuf>,
+
+        /// Overwrite the --to file if it already exists
+        #[arg(long, requires = "to")]
+        force: bool,
+
+        /// Copy to the clipboard instead of printing (needs the `clipboard` feature)
+        #[arg(long, conflicts_with = "to")]
+        to_clipboard: bool,
+    },
+
+    /// Import tasks from a JSON export or a markdown checklist
+    Import {
+        /// File to import from
+        path: PathBuf,
+
+        /// Input format (default: inferred from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+
+        /// Replace the whole list instead of merging into it
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// Interactive terminal UI for triaging tasks
+    #[cfg(feature = "tui")]
+    Tui,
+
+    /// Keep the list on screen and re-render it whenever the file changes
+    Watch,
+
+    /// Run the last command that changed this list again, e.g. yesterday's `add`
+    #[command(visible_alias = "repeat-last")]
+    ReplayLast,
+
+    /// Compare this list with another file (e.g. a synced copy) before merging
+    Diff {
+        /// The other list file
+        other: PathBuf,
+    },
+
+    /// Show a table of available commands
+    Commands,
+}
+
+/// Failure classes that scripts can tell apart by exit code.
+#[derive(Debug)]
+pub enum TodoError {
+    /// Reading or writing the storage file failed (exit code 2).
+    Io(std::io::Error),
+    /// The storage file is not valid RON (exit code 3).
+    Parse(ron::error::SpannedError),
+    /// A bad index, date or option value (exit code 4).
+    InvalidInput(String),
+    /// The command line itself didn't parse (exit code 4).
+    Usage(clap::Error),
+    /// The storage file was written by a newer version (exit code 3).
+    UnsupportedVersion(u32),
+    /// An encrypted file didn't decrypt: wrong passphrase or damaged (exit code 3).
+    Decryption,
+}
+
+impl TodoError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            TodoError::Io(_) => 2,
+            TodoError::Parse(_) | TodoError::UnsupportedVersion(_) | TodoError::Decryption => 3,
+            TodoError::InvalidInput(_) | TodoError::Usage(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TodoError::Io(err) => write!(f, "{}", err),
+            TodoError::Parse(err) => write!(f, "{}", err),
+            TodoError::InvalidInput(msg) => write!(f, "{}", msg),
+            TodoError::Usage(err) => write!(f, "{}", err),
+            TodoError::UnsupportedVersion(version) => write!(
+                f,
+                "data file has schema version {}, but this todoster only understands up to {}; please upgrade",
+                version, SCHEMA_VERSION
+            ),
+            TodoError::Decryption => write!(f, "decryption failed: wrong passphrase or damaged file"),
+        }
+    }
+}
+
+impl std::error::Error for TodoError {}
+
+fn invalid_input(msg: impl Into<String>) -> anyhow::Error {
+    TodoError::InvalidInput(msg.into()).into()
+}
+
+/// Exit code for an error returned by `run_cli`: the code of the first
+/// `TodoError` in the chain, or 1 for anything unclassified.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+    .find_map(|e| e.downcast_ref::<TodoError>())
+    .map(TodoError::exit_code)
+    .unwrap_or(1)
+}
+
+/// Which day a week begins on, for ordering weekday repeats and any
+/// week-based grouping.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+
+    /// The first day of the week containing `date`.
+    pub fn start_of_week(self, date: NaiveDate) -> NaiveDate {
+        date - Duration::days(date.weekday().days_since(self.weekday()) as i64)
+    }
+}
+
+/// How `list` splits tasks into sections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Pinned, incomplete and complete sections.
+    #[default]
+    Status,
+    /// One section per tag, then untagged tasks.
+    Tag,
+}
+
+/// What `list --sort` orders incomplete tasks by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// High, medium, low, then no priority; oldest first within each.
+    Priority,
+    /// Oldest first.
+    Created,
+    /// Soonest deadline first, then tasks with no due date.
+    Due,
+}
+
+/// A part of each task's line that `list --columns` can pick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Index,
+    Text,
+    /// Progress towards a target, e.g. "(3/10)".
+    Progress,
+    /// Time spent against the estimate.
+    Time,
+    Tags,
+    Priority,
+    Due,
+    Repeat,
+    /// Current run of on-time completions, e.g. "(🔥 5)".
+    Streak,
+    /// Repeats missed before the task last reset, e.g. "(missed 6)".
+    Missed,
+    /// When a deferred task starts.
+    Start,
+}
+
+/// How `list` shows the time until a completed task repeats.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RepeatDisplay {
+    /// Days and hours, e.g. "repeat in 2d, 5hrs".
+    #[default]
+    Precise,
+    /// Whole calendar days: "repeat in 2d", "due today", "overdue 3d".
+    Days,
+}
+
+/// Basic ANSI terminal colors, as named in `config.ron`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnsiColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Grey,
+}
+
+impl AnsiColor {
+    fn code(self) -> u8 {
+        match self {
+            AnsiColor::Black => 30,
+            AnsiColor::Red => 31,
+            AnsiColor::Green => 32,
+            AnsiColor::Yellow => 33,
+            AnsiColor::Blue => 34,
+            AnsiColor::Magenta => 35,
+            AnsiColor::Cyan => 36,
+            AnsiColor::White => 37,
+            AnsiColor::Grey => 90,
+        }
+    }
+
+    fn paint(self, text: &str) -> String {
+        format!("\x1B[{}m{}\x1B[0m", self.code(), text)
+    }
+}
+
+/// Colors for one list: section headers and the task index accent.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Theme {
+    pub header: Option<AnsiColor>,
+    pub accent: Option<AnsiColor>,
+}
+
+/// Settings read from `config.ron` in the XDG config directory. Every
+/// field is optional; a missing file means all defaults.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub week_start: WeekStart,
+    pub repeat_display: RepeatDisplay,
+    /// Themes keyed by list name (the data file's name without `.ron`).
+    pub themes: HashMap<String, Theme>,
+    pub priority_weights: PriorityWeights,
+    /// Bulk `complete`/`delete` touching more tasks than this asks for
+    /// confirmation first (default `DEFAULT_BULK_THRESHOLD`).
+    pub bulk_threshold: Option<usize>,
+    /// strftime pattern for dates shown to people (default
+    /// `DEFAULT_DATE_FORMAT`); JSON and RON output always use RFC 3339.
+    pub date_format: Option<String>,
+    /// Deadlines this many days away or closer count as due soon (default
+    /// `DEFAULT_DUE_SOON_DAYS`).
+    pub due_soon_days: Option<i64>,
+    /// Days a task may be overdue before it's flagged more loudly (default
+    /// `DEFAULT_OVERDUE_GRACE_DAYS`).
+    pub overdue_grace_days: Option<i64>,
+    /// Longest task text `add`/`edit` accept, in characters (default
+    /// unlimited).
+    pub max_text_length: Option<usize>,
+    /// `list --sort` key used when none is given ("priority", "created" or "due").
+    /// Kept as text so a typo is a warning, not a config that won't load.
+    pub sort: Option<String>,
+    /// Reverse the configured `sort`.
+    pub sort_reverse: bool,
+    /// Save the data file without pretty printing (`--compact`).
+    pub compact: bool,
+    /// Move completed one-off tasks this many days old to the archive
+    /// whenever a command changes the list; 0 or unset never archives.
+    pub auto_archive_days: Option<i64>,
+    /// Column `list` wraps task lines at when `--wrap` isn't given; 0 turns
+    /// wrapping off, unset follows the terminal's width.
+    pub wrap: Option<usize>,
+    /// After a command that changes the list, print a line such as "3
+    /// added, 12 completed this week" (not with `--quiet`).
+    pub show_summary_on_exit: bool,
+}
+
+pub const DEFAULT_BULK_THRESHOLD: usize = 10;
+/// Tag marking tasks captured with `add --inbox`; `list` leaves them out.
+pub const INBOX_TAG: &str = "inbox";
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// RFC 3339 timestamps, used for every date with `--iso-dates`.
+pub const ISO_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+pub const DEFAULT_DUE_SOON_DAYS: i64 = 1;
+pub const DEFAULT_OVERDUE_GRACE_DAYS: i64 = 3;
+
+/// Where a deadline stands relative to today, in whole days.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DueStatus {
+    Later(i64),
+    Soon(i64),
+    Today,
+    Overdue(i64),
+    /// Overdue for longer than the grace period.
+    LongOverdue(i64),
+}
+
+impl DueStatus {
+    /// Due today, soon or overdue: listed ahead of other tasks.
+    pub fn is_pressing(self) -> bool {
+        !matches!(self, DueStatus::Later(_))
+    }
+}
+
+/// The "due soon" window and overdue grace period from the config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DueWindows {
+    pub soon_days: i64,
+    pub grace_days: i64,
+}
+
+impl Default for DueWindows {
+    fn default() -> Self {
+        Self {
+            soon_days: DEFAULT_DUE_SOON_DAYS,
+            grace_days: DEFAULT_OVERDUE_GRACE_DAYS,
+        }
+    }
+}
+
+impl DueWindows {
+    /// Where `item`'s deadline stands, if it has one.
+    pub fn status(&self, item: &TodoItem, now: DateTime<Local>) -> Option<DueStatus> {
+        let days = (item.due_date?.date_naive() - now.date_naive()).num_days();
+        Some(match days {
+            0 => DueStatus::Today,
+            1.. if days <= self.soon_days => DueStatus::Soon(days),
+            1.. => DueStatus::Later(days),
+            _ if -days <= self.grace_days => DueStatus::Overdue(-days),
+            _ => DueStatus::LongOverdue(-days),
+        })
+    }
+}
+
+/// How much a task counts towards `stats`' weighted progress, by priority.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct PriorityWeights {
+    pub high: u32,
+    pub medium: u32,
+    pub low: u32,
+    /// Tasks without a priority.
+    pub none: u32,
+}
+
+impl Default for PriorityWeights {
+    fn default() -> Self {
+        Self {
+            high: 3,
+            medium: 2,
+            low: 1,
+            none: 1,
+        }
+    }
+}
+
+impl PriorityWeights {
+    pub fn weight(&self, priority: Option<Priority>) -> u32 {
+        match priority {
+            Some(Priority::High) => self.high,
+            Some(Priority::Medium) => self.medium,
+            Some(Priority::Low) => self.low,
+            None => self.none,
+        }
+    }
+}
+
+impl Config {
+    /// Theme for this run: `--theme <name>` if given (which must exist),
+    /// otherwise whatever is configured for `list_name`, otherwise none.
+    pub fn theme_for(&self, list_name: &str, requested: Option<&str>) -> Result<Theme> {
+        match requested {
+            Some(name) => self.themes.get(name).copied().ok_or_else(|| {
+                let mut known: Vec<&str> = self.themes.keys().map(String::as_str).collect();
+                known.sort_unstable();
+                invalid_input(format!(
+                    "No theme named \"{}\" in config (configured: {})",
+                    name,
+                    if known.is_empty() { "none".to_string() } else { known.join(", ") }
+                ))
+            }),
+            None => Ok(self.themes.get(list_name).copied().unwrap_or_default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(path)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to read config: {}", path.display()))?;
+
+        let config: Config = ron::from_str(&contents)
+        .map_err(TodoError::Parse)
+        .with_context(|| format!("Failed to parse config: {}", path.display()))?;
+
+        // Catch a bad pattern here rather than as a panic while printing.
+        if let Some(format) = &config.date_format {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(invalid_input(format!("Invalid date_format in config: \"{}\"", format)));
+            }
+        }
+        let windows = [
+            ("due_soon_days", config.due_soon_days),
+            ("overdue_grace_days", config.overdue_grace_days),
+            ("auto_archive_days", config.auto_archive_days),
+        ];
+        for (name, days) in windows {
+            if let Some(days) = days.filter(|days| *days < 0) {
+                return Err(invalid_input(format!("{} in config can't be negative (got {})", name, days)));
+            }
+        }
+        if config.max_text_length == Some(0) {
+            return Err(invalid_input("max_text_length in config must be at least 1"));
+        }
+        let mut config = config;
+        if let Some(key) = config.sort.as_deref().filter(|key| SortKey::from_str(key, true).is_err()) {
+            eprintln!("Warning: unknown sort \"{}\" in config (expected priority, created or due); ignoring it.", key);
+            config.sort = None;
+        }
+        Ok(config)
+    }
+
+    /// The configured default sort for `list`, if any.
+    pub fn sort_key(&self) -> Option<SortKey> {
+        self.sort.as_deref().and_then(|key| SortKey::from_str(key, true).ok())
+    }
+
+    pub fn due_windows(&self) -> DueWindows {
+        DueWindows {
+            soon_days: self.due_soon_days.unwrap_or(DEFAULT_DUE_SOON_DAYS),
+            grace_days: self.overdue_grace_days.unwrap_or(DEFAULT_OVERDUE_GRACE_DAYS),
+        }
+    }
+}
+
+/// The parts of a task `template-save` captures.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Template {
+    pub text: String,
+    #[serde(default)]
+    pub repeat_days: Option<i64>,
+    #[serde(default)]
+    pub repeat_spec: Option<RepeatSpec>,
+    #[serde(default)]
+    pub repeat_preset: Option<RepeatPreset>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+}
+
+impl Template {
+    /// A new task with the template's fields, created `now`.
+    pub fn instantiate(&self, now: DateTime<Local>) -> TodoItem {
+        let mut item = TodoItem::new(self.text.clone(), self.repeat_days);
+        item.repeat_spec = self.repeat_spec.clone();
+        item.repeat_preset = self.repeat_preset;
+        item.add_tags(&self.tags);
+        item.priority = self.priority;
+        item.created_at = Some(now);
+        item
+    }
+}
+
+/// Named templates, kept in `templates.ron` next to `config.ron`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TemplateStore {
+    #[serde(default)]
+    pub templates: BTreeMap<String, Template>,
+}
+
+impl TemplateStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(TemplateStore::default());
+        }
+
+        let contents = fs::read_to_string(path)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to read templates: {}", path.display()))?;
+        ron::from_str(&contents)
+        .map_err(TodoError::Parse)
+        .with_context(|| format!("Failed to parse templates: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+            .map_err(TodoError::Io)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let ron_string = ron::ser::to_string_pretty(self, PrettyConfig::new())
+        .with_context(|| "Failed to serialize templates")?;
+        fs::write(path, ron_string)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to write templates: {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Template> {
+        self.templates.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+            invalid_input(format!(
+                "No template named \"{}\" (saved: {})",
+                name,
+                if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            ))
+        })
+    }
+}
+
+/// Repeat schedules other than a plain "every N days" interval, which
+/// stays in `TodoItem::repeat_days`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RepeatSpec {
+    /// Due again on the next of these days of the week.
+    Weekdays(Vec<Weekday>),
+    /// Due again this many hours after the moment it was completed.
+    Hours(i64),
+    /// Due again on the same day this many months on (or the month's last
+    /// day, for the 31st of a shorter month).
+    Months(u32),
+}
+
+/// Named `--repeat` values. The name is kept with the task so `list` can
+/// show it instead of the schedule it stands for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepeatPreset {
+    Daily,
+    Weekly,
+    Fortnightly,
+    Monthly,
+    /// Monday to Friday.
+    Weekday,
+}
+
+impl RepeatPreset {
+    pub fn name(self) -> &'static str {
+        match self {
+            RepeatPreset::Daily => "daily",
+            RepeatPreset::Weekly => "weekly",
+            RepeatPreset::Fortnightly => "fortnightly",
+            RepeatPreset::Monthly => "monthly",
+            RepeatPreset::Weekday => "weekday",
+        }
+    }
+
+    /// The `repeat_days` and `repeat_spec` the preset sets.
+    pub fn schedule(self) -> (Option<i64>, Option<RepeatSpec>) {
+        match self {
+            RepeatPreset::Daily => (Some(1), None),
+            RepeatPreset::Weekly => (Some(7), None),
+            RepeatPreset::Fortnightly => (Some(14), None),
+            RepeatPreset::Monthly => (None, Some(RepeatSpec::Months(1))),
+            RepeatPreset::Weekday => (
+                None,
+                Some(RepeatSpec::Weekdays(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri])),
+            ),
+        }
+    }
+}
+
+/// A `--repeat` value: whole days ("3"), hours with an "h" suffix ("4h")
+/// or a preset name ("weekly").
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepeatInterval {
+    Days(i64),
+    Hours(i64),
+    Preset(RepeatPreset),
+}
+
+impl std::str::FromStr for RepeatInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let spec = s.trim().to_ascii_lowercase();
+        let preset = match spec.as_str() {
+            "daily" => Some(RepeatPreset::Daily),
+            "weekly" => Some(RepeatPreset::Weekly),
+            "fortnightly" => Some(RepeatPreset::Fortnightly),
+            "monthly" => Some(RepeatPreset::Monthly),
+            "weekday" | "weekdays" => Some(RepeatPreset::Weekday),
+            _ => None,
+        };
+        if let Some(preset) = preset {
+            return Ok(RepeatInterval::Preset(preset));
+        }
+        match spec.strip_suffix('h') {
+            Some(hours) => hours.trim().parse().map(RepeatInterval::Hours),
+            None => spec.strip_suffix('d').unwrap_or(&spec).trim().parse().map(RepeatInterval::Days),
+        }
+        .map_err(|_| {
+            format!(
+                "expected days (e.g. \"3\"), hours (e.g. \"4h\") or daily / weekly / fortnightly / monthly / weekday, got \"{}\"",
+                s
+            )
+        })
+    }
+}
+
+impl RepeatInterval {
+    /// Intervals must be at least one day, or one hour for hourly repeats.
+    pub fn validate(self) -> Result<Self> {
+        match self {
+            RepeatInterval::Days(days) => validate_repeat_days(days).map(RepeatInterval::Days),
+            RepeatInterval::Hours(hours) if hours < 1 => Err(invalid_input(format!(
+                "Repeat interval must be at least 1 hour (got {}h).",
+                hours
+            ))),
+            RepeatInterval::Hours(_) | RepeatInterval::Preset(_) => Ok(self),
+        }
+    }
+
+    /// The `repeat_days`, `repeat_spec` and `repeat_preset` that a task (or
+    /// template) repeating this way stores.
+    pub fn schedule(self) -> (Option<i64>, Option<RepeatSpec>, Option<RepeatPreset>) {
+        match self {
+            RepeatInterval::Days(days) => (Some(days), None, None),
+            RepeatInterval::Hours(hours) => (None, Some(RepeatSpec::Hours(hours)), None),
+            RepeatInterval::Preset(preset) => {
+                let (days, spec) = preset.schedule();
+                (days, spec, Some(preset))
+            }
+        }
+    }
+}
+
+/// How important a task is; ordered from low to high.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    /// todo.txt's priority letter: high is (A), medium (B), low (C).
+    fn letter(self) -> char {
+        match self {
+            Priority::High => 'A',
+            Priority::Medium => 'B',
+            Priority::Low => 'C',
+        }
+    }
+
+    /// The reverse of `letter`; anything below (B) counts as low.
+    fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'A' => Some(Priority::High),
+            'B' => Some(Priority::Medium),
+            'C'..='Z' => Some(Priority::Low),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Ron,
+    Markdown,
+    Todotxt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Json,
+    Markdown,
+    Todotxt,
+}
+
+impl ExportFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "ron" => Some(ExportFormat::Ron),
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "txt" => Some(ExportFormat::Todotxt),
+            _ => None,
+        }
+    }
+}
+
+impl ImportFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(ImportFormat::Json),
+            "md" | "markdown" => Some(ImportFormat::Markdown),
+            "txt" => Some(ImportFormat::Todotxt),
+            _ => None,
+        }
+    }
+}
+
+impl Commands {
+    /// Commit message for `--git`, or `None` for commands that never write.
+    fn git_message(&self) -> Option<&'static str> {
+        match self {
+            Commands::Add { .. } => Some("todoster: add task"),
+            Commands::Import { .. } => Some("todoster: import tasks"),
+            Commands::AddFromTemplate { .. } => Some("todoster: add task from template"),
+            Commands::Complete { dry_run: true, .. } => None,
+            Commands::Complete { .. } => Some("todoster: complete task"),
+            Commands::Undo { .. } => Some("todoster: undo tasks"),
+            Commands::Toggle { .. } => Some("todoster: toggle tasks"),
+            Commands::RestoreSnapshot { .. } => Some("todoster: restore snapshot"),
+            Commands::Skip { .. } => Some("todoster: skip task"),
+            Commands::Bump { .. } => Some("todoster: bump repeat"),
+            Commands::Progress { .. } => Some("todoster: record progress"),
+            Commands::Track { .. } => Some("todoster: track time"),
+            Commands::Pin { .. } => Some("todoster: pin task"),
+            Commands::Unpin { .. } => Some("todoster: unpin task"),
+            Commands::Focus { .. } => Some("todoster: focus task"),
+            Commands::Unfocus => Some("todoster: leave focus"),
+            Commands::Edit { .. } => Some("todoster: edit task"),
+            Commands::Delete { confirm: true, .. } => Some("todoster: delete tasks"),
+            Commands::Tag { .. } => Some("todoster: tag tasks"),
+            Commands::Triage { .. } => Some("todoster: triage task"),
+            Commands::Merge { confirm: true, .. } => Some("todoster: merge tasks"),
+            Commands::Dedupe { confirm: true, .. } => Some("todoster: remove duplicate tasks"),
+            Commands::Replace { confirm: true, .. } => Some("todoster: replace task text"),
+            #[cfg(feature = "tui")]
+            Commands::Tui => Some("todoster: tui session"),
+            Commands::List { .. }
+            | Commands::Search { .. }
+            | Commands::Delete { .. }
+            | Commands::Merge { .. }
+            | Commands::Dedupe { .. }
+            | Commands::Replace { .. }
+            | Commands::Export { .. }
+            | Commands::Open { .. }
+            | Commands::Inbox
+            | Commands::Remind
+            | Commands::TemplateSave { .. }
+            | Commands::Templates
+            | Commands::Random { .. }
+            | Commands::History
+            | Commands::Doctor { .. }
+            | Commands::EditFile
+            | Commands::Version
+            | Commands::Stats { .. }
+            | Commands::StatsTask { .. }
+            | Commands::Watch
+            | Commands::ReplayLast
+            | Commands::Diff { .. }
+            | Commands::Commands => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TodoItem {
+    /// Stable handle for `#<id>` task references, unlike the index. 0 until
+    /// the list hands one out (see `TodoList::assign_ids`).
+    #[serde(default)]
+    pub id: u64,
+    pub text: String,
+    pub complete: bool,
+    #[serde(default, deserialize_with = "lenient_date")]
+    pub complete_date: Option<DateTime<Local>>,
+    pub repeat_days: Option<i64>,
+    #[serde(default)]
+    pub start_date: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Last day a repeat may fall on; after that the task stays complete.
+    #[serde(default)]
+    pub repeat_until: Option<DateTime<Local>>,
+    /// How many more times the task may repeat; decremented on each reset.
+    #[serde(default)]
+    pub repeat_count: Option<u32>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Local>>,
+    /// Every recorded completion, oldest first.
+    #[serde(default)]
+    pub completion_history: Vec<DateTime<Local>>,
+    #[serde(default)]
+    pub repeat_spec: Option<RepeatSpec>,
+    /// Note given with the latest `complete`; cleared when the task resets.
+    #[serde(default)]
+    pub last_completion_note: Option<String>,
+    /// Amount to reach before the task counts as done (e.g. pages read).
+    #[serde(default)]
+    pub target: Option<u32>,
+    /// Progress towards `target` in the current cycle.
+    #[serde(default)]
+    pub progress: u32,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Set by `focus`; while any task has it, `list` shows only that one.
+    #[serde(default)]
+    pub focused: bool,
+    /// Time of day a repeat falls due; midnight for weekday repeats and
+    /// the completion time for interval repeats when unset.
+    #[serde(default)]
+    pub due_time: Option<NaiveTime>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Expected time in minutes.
+    #[serde(default)]
+    pub estimate_minutes: Option<u32>,
+    /// Time logged with `track`, in minutes; kept across repeats.
+    #[serde(default)]
+    pub spent_minutes: u32,
+    /// Deadline for the task (midnight of the day unless given a time).
+    #[serde(default)]
+    pub due_date: Option<DateTime<Local>>,
+    /// Repeats that fell due before the task last reset, beyond the one it
+    /// reset for; 0 with `--no-catchup`.
+    #[serde(default)]
+    pub missed_count: u32,
+    /// Longest run of on-time completions so far (the current run is
+    /// worked out from `completion_history`).
+    #[serde(default)]
+    pub best_streak: u32,
+    /// A link the task is about, always http:// or https://.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// A repeat that would fall due on a Saturday or Sunday moves to the
+    /// Monday after.
+    #[serde(default)]
+    pub skip_weekends: bool,
+    /// The `--repeat` preset the schedule came from, shown by name while
+    /// the schedule still matches it.
+    #[serde(default)]
+    pub repeat_preset: Option<RepeatPreset>,
+    /// How long before `due_date` `remind` starts listing the task.
+    #[serde(default)]
+    pub reminder_lead_minutes: Option<i64>,
+}
+
+/// How a stored date was written, before it's interpreted.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDate {
+    Text(String),
+    Other(serde::de::IgnoredAny),
+}
+
+/// Read a stored date, accepting the formats hand edits and older
+/// versions tend to leave behind. A date that can't be read becomes `None`
+/// (with a warning) instead of failing the whole file.
+fn lenient_date<'de, D>(deserializer: D) -> std::result::Result<Option<DateTime<Local>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = match Option::<StoredDate>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(StoredDate::Text(text)) => text,
+        Some(StoredDate::Other(_)) => {
+            eprintln!("Warning: ignoring a complete_date that isn't a date string.");
+            return Ok(None);
+        }
+    };
+    if let Ok(date) = text.parse::<DateTime<Local>>() {
+        return Ok(Some(date));
+    }
+
+    let parsed = DateTime::parse_from_rfc3339(text.trim())
+    .or_else(|_| DateTime::parse_from_rfc2822(text.trim()))
+    .map(|date| date.with_timezone(&Local))
+    .ok()
+    .or_else(|| {
+        ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(text.trim(), format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+    });
+    match parsed {
+        Some(date) => eprintln!("Warning: read complete_date \"{}\" as {}.", text, date.to_rfc3339()),
+        None => eprintln!("Warning: could not read complete_date \"{}\"; dropping it.", text),
+    }
+    Ok(parsed)
+}
+
+/// Schema version written to new and upgraded data files. Bump it (and
+/// add a step to `TodoList::migrate`) when stored data needs converting.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TodoList {
+    /// Schema version of the stored file; files from before versioning
+    /// have none and count as version 0.
+    #[serde(default)]
+    pub version: u32,
+    pub items: Vec<TodoItem>,
+    /// The id the next new task gets. Only ever grows, so a deleted task's
+    /// id is never reused.
+    #[serde(default)]
+    pub next_id: u64,
+    /// Set when the list was loaded with a passphrase; `save` then writes
+    /// it encrypted. Never stored.
+    #[serde(skip)]
+    pub passphrase: Option<Passphrase>,
+    /// Decides which deadlines are pressing enough to list first. Comes
+    /// from the config; never stored.
+    #[serde(skip)]
+    pub due_windows: DueWindows,
+    /// Save on a single line instead of pretty-printed: much smaller and
+    /// free of the `/*[n]*/` index comments that shift on every insert, but
+    /// hard to edit by hand. Never stored.
+    #[serde(skip)]
+    pub compact: bool,
+}
+
+impl Default for TodoList {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            items: Vec::new(),
+            next_id: 1,
+            passphrase: None,
+            due_windows: DueWindows::default(),
+            compact: false,
+        }
+    }
+}
+
+/// A task whose saved state can't have come from normal use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthIssue {
+    CompleteWithoutDate(usize),
+    NonPositiveRepeat(usize, i64),
+    NonPositiveHourlyRepeat(usize, i64),
+    EmptyWeekdays(usize),
+    ZeroTarget(usize),
+    DuplicateId(usize, u64),
+}
+
+impl fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthIssue::CompleteWithoutDate(idx) => write!(f, "[{}] is complete but has no complete_date", idx),
+            HealthIssue::NonPositiveRepeat(idx, days) => write!(f, "[{}] repeats every {} day(s)", idx, days),
+            HealthIssue::NonPositiveHourlyRepeat(idx, hours) => write!(f, "[{}] repeats every {} hour(s)", idx, hours),
+            HealthIssue::EmptyWeekdays(idx) => write!(f, "[{}] repeats on weekdays, but none are listed", idx),
+            HealthIssue::ZeroTarget(idx) => write!(f, "[{}] has a target of 0", idx),
+            HealthIssue::DuplicateId(idx, id) => write!(f, "[{}] has id #{}, already used by an earlier task", idx, id),
+        }
+    }
+}
+
+/// Just enough of a data file to read its version, whatever the rest holds.
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: u32,
+}
+
+impl TodoItem {
+    pub fn new(text: String, repeat_days: Option<i64>) -> Self {
+        Self {
+            text,
+            complete: false,
+            complete_date: None,
+            repeat_days,
+            start_date: None,
+            tags: Vec::new(),
+            repeat_until: None,
+            repeat_count: None,
+            notes: None,
+            created_at: None,
+            completion_history: Vec::new(),
+            repeat_spec: None,
+            last_completion_note: None,
+            target: None,
+            progress: 0,
+            pinned: false,
+            focused: false,
+            due_time: None,
+            priority: None,
+            estimate_minutes: None,
+            spent_minutes: 0,
+            due_date: None,
+            missed_count: 0,
+            best_streak: 0,
+            url: None,
+            skip_weekends: false,
+            repeat_preset: None,
+            reminder_lead_minutes: None,
+            id: 0,
+        }
+    }
+
+    pub fn mark_complete(&mut self, now: DateTime<Local>) {
+        self.complete = true;
+        self.complete_date = Some(now);
+        self.completion_history.push(now);
+        self.best_streak = self.best_streak.max(self.current_streak(now));
+    }
+
+    /// Consecutive on-time completions of a repeating task, newest back:
+    /// each one came before the occurrence after the one it was due for.
+    /// Drops to 0 once an occurrence has been missed since the last one.
+    pub fn current_streak(&self, now: DateTime<Local>) -> u32 {
+        // The occurrence after the one due following `done`: reaching it
+        // without another completion means an occurrence was missed.
+        let deadline = |done| self.due_after(self.due_after(done)?);
+        let Some(&last) = self.completion_history.last().filter(|_| self.is_repeating()) else {
+            return 0;
+        };
+        if deadline(last).is_some_and(|deadline| deadline <= now) {
+            return 0;
+        }
+
+        let mut streak = 1;
+        for pair in self.completion_history.windows(2).rev() {
+            match deadline(pair[0]) {
+                Some(deadline) if pair[1] < deadline => streak += 1,
+                _ => break,
+            }
+        }
+        streak
+    }
+
+    /// Complete an incomplete task or undo a completed one. Returns the new
+    /// state (true: complete).
+    pub fn toggle(&mut self, now: DateTime<Local>) -> bool {
+        if self.complete {
+            self.mark_incomplete();
+        } else {
+            self.mark_complete(now);
+        }
+        self.complete
+    }
+
+    /// Move a repeating task on by one occurrence without recording a
+    /// completion. A task that is currently due is treated as if done now;
+    /// one that is already complete has its anchor moved to the upcoming
+    /// due date, so it stays complete until the occurrence after that.
+    /// Returns false (and changes nothing) for non-repeating tasks.
+    pub fn skip_occurrence(&mut self, now: DateTime<Local>) -> bool {
+        if !self.is_repeating() {
+            return false;
+        }
+
+        let anchor = match self.next_due_start() {
+            Some(next_due) if self.complete => next_due,
+            _ => now,
+        };
+        self.complete = true;
+        self.complete_date = Some(anchor);
+        true
+    }
+
+    pub fn mark_incomplete(&mut self) {
+        // Undoing a completion takes it back out of the history too.
+        if let Some(done_at) = self.complete_date {
+            if self.completion_history.last() == Some(&done_at) {
+                self.completion_history.pop();
+            }
+        }
+        self.complete = false;
+        self.complete_date = None;
+        self.last_completion_note = None;
+    }
+
+    /// Add to the progress of a task with a target, completing it once the
+    /// target is reached. Returns true if this call completed the task.
+    pub fn add_progress(&mut self, amount: u32, now: DateTime<Local>) -> bool {
+        let Some(target) = self.target else {
+            return false;
+        };
+
+        self.progress = self.progress.saturating_add(amount).min(target);
+        if self.progress == target && !self.complete {
+            self.mark_complete(now);
+            return true;
+        }
+        false
+    }
+
+    /// Gaps between consecutive recorded completions, in days.
+    pub fn completion_gaps_days(&self) -> Vec<f64> {
+        self.completion_history
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_seconds() as f64 / 86_400.0)
+        .collect()
+    }
+
+    /// Mean and median gap between completions in days, if at least two
+    /// completions have been recorded.
+    pub fn completion_gap_stats(&self) -> Option<(f64, f64)> {
+        let mut gaps = self.completion_gaps_days();
+        if gaps.is_empty() {
+            return None;
+        }
+
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        gaps.sort_by(|a, b| a.total_cmp(b));
+        let mid = gaps.len() / 2;
+        let median = if gaps.len().is_multiple_of(2) {
+            (gaps[mid - 1] + gaps[mid]) / 2.0
+        } else {
+            gaps[mid]
+        };
+        Some((mean, median))
+    }
+
+    pub fn is_repeating(&self) -> bool {
+        self.repeat_days.is_some() || self.repeat_spec.is_some()
+    }
+
+    /// Whether `other` has the same completion and repeat settings, which is
+    /// what `diff` compares.
+    pub fn same_state(&self, other: &TodoItem) -> bool {
+        self.complete == other.complete
+            && (!self.complete || self.complete_date == other.complete_date)
+            && self.repeat_days == other.repeat_days
+            && self.repeat_spec == other.repeat_spec
+            && self.due_time == other.due_time
+            && self.skip_weekends == other.skip_weekends
+            && self.repeat_until == other.repeat_until
+            && self.repeat_count == other.repeat_count
+    }
+
+    /// Repeat every `interval`, replacing any other kind of repeat.
+    pub fn set_repeat_interval(&mut self, interval: RepeatInterval) {
+        (self.repeat_days, self.repeat_spec, self.repeat_preset) = interval.schedule();
+    }
+
+    /// When a completed repeating task becomes due again. Weekday repeats
+    /// are due from midnight of the next matching day after completion, and
+    /// a `due_time` moves either kind of repeat to that time on its due day.
+    /// Hourly repeats count from the moment of completion and ignore it.
+    pub fn next_due_start(&self) -> Option<DateTime<Local>> {
+        self.due_after(self.complete_date?)
+    }
+
+    /// The first occurrence of the repeat after `done_at`.
+    fn due_after(&self, done_at: DateTime<Local>) -> Option<DateTime<Local>> {
+        let due = match &self.repeat_spec {
+            Some(RepeatSpec::Weekdays(days)) => {
+                let done_on = done_at.date_naive();
+                (1..=7)
+                .map(|ahead| done_on + Duration::days(ahead))
+                .find(|date| days.contains(&date.weekday()))
+                .and_then(|date| local_at(date, self.due_time.unwrap_or(NaiveTime::MIN)))
+            }
+            Some(RepeatSpec::Hours(hours)) => Some(done_at + Duration::hours(*hours)),
+            Some(RepeatSpec::Months(months)) => {
+                let due = done_at.checked_add_months(chrono::Months::new(*months))?;
+                match self.due_time {
+                    Some(time) => local_at(due.date_naive(), time),
+                    None => Some(due),
+                }
+            }
+            None => {
+                let due = done_at + Duration::days(self.repeat_days?);
+                match self.due_time {
+                    Some(time) => local_at(due.date_naive(), time),
+                    None => Some(due),
+                }
+            }
+        }?;
+        let weekend_days = match due.weekday() {
+            Weekday::Sat if self.skip_weekends => 2,
+            Weekday::Sun if self.skip_weekends => 1,
+            _ => 0,
+        };
+        Some(due + Duration::days(weekend_days))
+    }
+
+    /// True once a limited repeat has used up its count or would next fall
+    /// after `repeat_until`.
+    pub fn repeat_finished(&self) -> bool {
+        if self.repeat_count == Some(0) {
+            return true;
+        }
+
+        match (self.repeat_until, self.next_due_start()) {
+            (Some(until), Some(next_due)) => next_due.date_naive() > until.date_naive(),
+            _ => false,
+        }
+    }
+
+    /// How many more occurrences fell due by `now` after the next one, i.e.
+    /// the ones missed while the task sat completed. Occurrences past
+    /// `repeat_until` don't count.
+    pub fn missed_occurrences(&self, now: DateTime<Local>) -> u32 {
+        let Some(mut due) = self.next_due_start() else {
+            return 0;
+        };
+        let mut missed = 0;
+        while let Some(next) = self.due_after(due).filter(|next| *next <= now && *next > due) {
+            if self.repeat_until.is_some_and(|until| next.date_naive() > until.date_naive()) {
+                break;
+            }
+            missed += 1;
+            due = next;
+        }
+        missed
+    }
+
+    pub fn should_reset(&self, now: DateTime<Local>) -> bool {
+        if !self.complete || self.repeat_finished() {
+            return false;
+        }
+
+        match self.next_due_start() {
+            Some(next_due) => now >= next_due,
+            None => false,
+        }
+    }
+
+    pub fn reset_if_due(&mut self, now: DateTime<Local>) {
+        if self.should_reset(now) {
+            self.complete = false;
+            self.complete_date = None;
+            self.last_completion_note = None;
+            self.progress = 0;
+            if let Some(count) = self.repeat_count.as_mut() {
+                *count -= 1;
+            }
+        }
+    }
+
+    /// Add tags (ignoring any leading `#`), dropping duplicates.
+    /// Returns true if the tag set changed.
+    pub fn add_tags(&mut self, tags: &[String]) -> bool {
+        let before = self.tags.clone();
+        for tag in tags {
+            let tag = normalize_tag(tag);
+            if !tag.is_empty() {
+                self.tags.push(tag);
+            }
+        }
+        self.dedupe_tags();
+        self.tags != before
+    }
+
+    /// Captured with `add --inbox` and not yet triaged.
+    pub fn in_inbox(&self) -> bool {
+        self.tags.iter().any(|tag| tag == INBOX_TAG)
+    }
+
+    /// Remove tags (ignoring any leading `#`). Returns true if the tag set changed.
+    pub fn remove_tags(&mut self, tags: &[String]) -> bool {
+        let before = self.tags.clone();
+        let remove: Vec<String> = tags.iter().map(|t| normalize_tag(t)).collect();
+        self.tags.retain(|t| !remove.contains(t));
+        self.dedupe_tags();
+        self.tags != before
+    }
+
+    fn dedupe_tags(&mut self) {
+        let mut seen = Vec::new();
+        self.tags.retain(|t| {
+            if seen.contains(t) {
+                false
+            } else {
+                seen.push(t.clone());
+                true
+            }
+        });
+    }
+
+    /// Fold a duplicate into this task: tags are unioned, notes appended,
+    /// the earliest `created_at` kept, and the more complete state wins
+    /// (complete beats incomplete, then the latest completion).
+    pub fn merge_from(&mut self, other: &TodoItem) {
+        self.add_tags(&other.tags);
+
+        self.notes = match (self.notes.take(), &other.notes) {
+            (Some(mine), Some(theirs)) => Some(format!("{}\n{}", mine, theirs)),
+            (mine, theirs) => mine.or_else(|| theirs.clone()),
+        };
+
+        self.created_at = match (self.created_at, other.created_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        self.spent_minutes = self.spent_minutes.saturating_add(other.spent_minutes);
+        self.estimate_minutes = self.estimate_minutes.or(other.estimate_minutes);
+
+        self.completion_history.extend(other.completion_history.iter().copied());
+        self.completion_history.sort();
+        self.completion_history.dedup();
+
+        let other_is_more_complete = match (self.complete, other.complete) {
+            (false, true) => true,
+            (true, true) => other.complete_date > self.complete_date,
+            _ => false,
+        };
+        if other_is_more_complete {
+            self.complete = other.complete;
+            self.complete_date = other.complete_date;
+            self.last_completion_note = other.last_completion_note.clone();
+        }
+    }
+
+    /// A repeating task that is waiting to be done, or whose next repeat
+    /// falls today (or earlier).
+    pub fn is_repeat_due_today(&self, now: DateTime<Local>) -> bool {
+        if !self.is_repeating() || self.is_deferred(now) {
+            return false;
+        }
+        if !self.complete {
+            return true;
+        }
+        if self.repeat_finished() {
+            return false;
+        }
+        self.next_due_start()
+        .is_some_and(|next_due| next_due.date_naive() <= now.date_naive())
+    }
+
+    /// When the task next needs doing: its deadline if it has one, else for
+    /// a repeat either `now` (waiting to be done) or its next occurrence.
+    /// `None` for finished tasks and tasks with no date at all.
+    pub fn next_due(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self.complete {
+            false => self.due_date.or_else(|| self.is_repeating().then_some(now)),
+            true if self.is_repeating() && !self.repeat_finished() => self.next_due_start(),
+            true => None,
+        }
+    }
+
+    /// Time left before the deadline while `now` is within the reminder
+    /// lead time of it: from exactly the lead time before, up to the
+    /// deadline itself.
+    pub fn reminder_countdown(&self, now: DateTime<Local>) -> Option<Duration> {
+        if self.complete {
+            return None;
+        }
+        let left = self.due_date? - now;
+        let lead = Duration::minutes(self.reminder_lead_minutes?);
+        (left >= Duration::zero() && left <= lead).then_some(left)
+    }
+
+    pub fn is_deferred(&self, now: DateTime<Local>) -> bool {
+        matches!(self.start_date, Some(start) if start > now)
+    }
+
+    pub fn time_until_next_repeat(&self, now: DateTime<Local>) -> Option<Duration> {
+        if !self.complete || self.repeat_finished() {
+            return None;
+        }
+        self.next_due_start().map(|next_due| next_due - now)
+    }
+}
+
+/// A task plus fields derived from it at export time, so downstream tools
+/// don't have to redo the date math.
+#[derive(Serialize)]
+pub struct ComputedItem<'a> {
+    #[serde(flatten)]
+    pub item: &'a TodoItem,
+    /// Days past the next repeat (negative: days still to go). `None` unless
+    /// the task is a completed, still-active repeat.
+    pub overdue_days: Option<i64>,
+    /// Whole days since `created_at`, if known.
+    pub age_days: Option<i64>,
+}
+
+/// Task indexes in the order `list` shows them, section by section.
+#[derive(Debug, Default)]
+pub struct ListSections {
+    pub pinned: Vec<usize>,
+    pub incomplete: Vec<usize>,
+    pub complete: Vec<usize>,
+    /// Deferred tasks left out.
+    pub hidden: usize,
+}
+
+/// Whole-list counts behind `stats`, shared by the table and `--json`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ListStats {
+    pub total: usize,
+    pub complete: usize,
+    pub incomplete: usize,
+    pub repeating: usize,
+    /// Repeating tasks due today or overdue (see `is_repeat_due_today`).
+    pub overdue: usize,
+    /// Age of the oldest incomplete task with a known `created_at`.
+    pub oldest_incomplete_days: Option<i64>,
+    /// Percentage done with each task weighted by priority, to one decimal
+    /// place; `None` for an empty list (or all-zero weights).
+    pub weighted_progress: Option<f64>,
+}
+
+/// What `diff` found between two lists. Tasks are matched by trimmed text,
+/// duplicates pairing up in list order.
+#[derive(Debug, Default, PartialEq)]
+pub struct ListDiff {
+    /// Indexes of tasks only in this list.
+    pub only_here: Vec<usize>,
+    /// Indexes of tasks only in the other list.
+    pub only_there: Vec<usize>,
+    /// Tasks in both (this index, the other's) whose completion or repeat
+    /// settings differ.
+    pub changed: Vec<(usize, usize)>,
+}
+
+impl ListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_here.is_empty() && self.only_there.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+struct ComputedList<'a> {
+    items: Vec<ComputedItem<'a>>,
+}
+
+impl<'a> ComputedItem<'a> {
+    pub fn new(item: &'a TodoItem, now: DateTime<Local>) -> Self {
+        Self {
+            item,
+            overdue_days: item.time_until_next_repeat(now).map(|diff| -diff.num_days()),
+            age_days: item.created_at.map(|created| (now - created).num_days()),
+        }
+    }
+}
+
+impl TodoList {
+    fn load(path: &PathBuf, passphrase: Option<&Passphrase>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(TodoList {
+                passphrase: passphrase.cloned(),
+                ..TodoList::default()
+            });
+        }
+
+        let contents = read_data(path, passphrase)?;
+        let mut list = TodoList::parse(&contents).with_context(|| format!("Cannot read {}", path.display()))?;
+        list.passphrase = passphrase.cloned();
+        // Not saved here (reading never rewrites the file), but handed out
+        // the same way every time until the next save stores them.
+        list.assign_ids();
+
+        if list.migrate()? {
+            list.save(path)?;
+        }
+        Ok(list)
+    }
+
+    /// Read a list that is only looked at (`diff`, `--merge-files`). It is
+    /// migrated in memory but never saved, so the file stays exactly as it
+    /// was, encryption included.
+    fn read_only(path: &Path, passphrase: Option<&Passphrase>) -> Result<Self> {
+        let contents = read_data(path, passphrase)?;
+        let mut list = TodoList::parse(&contents).with_context(|| format!("Cannot read {}", path.display()))?;
+        list.assign_ids();
+        list.migrate()?;
+        Ok(list)
+    }
+
+    /// Parse the text of a data file, without migrating it.
+    fn parse(contents: &str) -> Result<Self> {
+        // Check the version first so a newer file fails clearly instead of
+        // as a confusing parse error.
+        if let Ok(probe) = ron::from_str::<VersionProbe>(contents) {
+            if probe.version > SCHEMA_VERSION {
+                return Err(TodoError::UnsupportedVersion(probe.version).into());
+            }
+        }
+
+        ron::from_str(contents)
+        .map_err(TodoError::Parse)
+        .with_context(|| "Failed to parse RON data")
+    }
+
+    /// Upgrade a list read from an older file to `SCHEMA_VERSION`. Returns
+    /// true if anything changed (so the caller can rewrite the file).
+    pub fn migrate(&mut self) -> Result<bool> {
+        if self.version > SCHEMA_VERSION {
+            return Err(TodoError::UnsupportedVersion(self.version).into());
+        }
+        if self.version == SCHEMA_VERSION {
+            return Ok(false);
+        }
+
+        // 0 -> 1: the unversioned format. Fields added since then are
+        // `#[serde(default)]`, so parsing has already filled them in.
+        self.version = SCHEMA_VERSION;
+        Ok(true)
+    }
+
+    fn save(&self, path: &PathBuf) -> Result<()> {
+        // Make sure the directory exists (for ~/.local/share/todoster/todos.ron)
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+            .map_err(TodoError::Io)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let ron_string = match self.compact {
+            true => ron::ser::to_string(self).with_context(|| "Failed to serialize RON")?,
+            false => self.to_ron()?,
+        };
+        let data = match &self.passphrase {
+            Some(passphrase) => encrypt_data(ron_string.as_bytes(), passphrase)?,
+            None => ron_string.into_bytes(),
+        };
+
+        let mut file = fs::File::create(path)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to create file: {}", path.display()))?;
+        file.write_all(&data)
+        .map_err(TodoError::Io)
+        .with_context(|| "Failed to write RON data")?;
+        Ok(())
+    }
+
+    /// Indexes of items matching the delete selectors. The status flags are
+    /// alternatives (either state may match); a tag narrows the result.
+    fn select(&self, tag: Option<&str>, completed: bool, incomplete: bool) -> Vec<usize> {
+        self.items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| {
+            let status_ok = (!completed && !incomplete)
+            || (completed && item.complete)
+            || (incomplete && !item.complete);
+            let tag_ok = tag.is_none_or(|t| item.tags.iter().any(|it| it == t));
+            status_ok && tag_ok
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+    }
+
+    /// Groups of tasks with identical trimmed text, in list order. The first
+    /// index of each group is the copy to keep: a dated completion wins,
+    /// then the earliest `created_at`, then the earliest position.
+    pub fn duplicate_groups(&self, ignore_case: bool) -> Vec<Vec<usize>> {
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for (idx, item) in self.items.iter().enumerate() {
+            let key = match ignore_case {
+                true => item.text.trim().to_lowercase(),
+                false => item.text.trim().to_string(),
+            };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push(idx),
+                None => groups.push((key, vec![idx])),
+            }
+        }
+
+        groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(_, mut members)| {
+            let keep = *members
+            .iter()
+            .min_by_key(|idx| {
+                let item = &self.items[**idx];
+                let done = item.complete && item.complete_date.is_some();
+                (!done, item.created_at.is_none(), item.created_at, **idx)
+            })
+            .unwrap();
+            members.retain(|idx| *idx != keep);
+            members.insert(0, keep);
+            members
+        })
+        .collect()
+    }
+
+    /// Tasks whose saved state is inconsistent, in list order (see `doctor`).
+    pub fn health_issues(&self) -> Vec<HealthIssue> {
+        let mut issues = Vec::new();
+        let mut ids = HashSet::new();
+        for (idx, item) in self.items.iter().enumerate() {
+            if item.complete && item.complete_date.is_none() {
+                issues.push(HealthIssue::CompleteWithoutDate(idx));
+            }
+            if let Some(days) = item.repeat_days.filter(|days| *days < 1) {
+                issues.push(HealthIssue::NonPositiveRepeat(idx, days));
+            }
+            if matches!(&item.repeat_spec, Some(RepeatSpec::Weekdays(days)) if days.is_empty()) {
+                issues.push(HealthIssue::EmptyWeekdays(idx));
+            }
+            if let Some(RepeatSpec::Hours(hours @ ..=0)) = item.repeat_spec {
+                issues.push(HealthIssue::NonPositiveHourlyRepeat(idx, hours));
+            }
+            if item.target == Some(0) {
+                issues.push(HealthIssue::ZeroTarget(idx));
+            }
+            // 0 is "not assigned yet", which `load` fills in.
+            if item.id != 0 && !ids.insert(item.id) {
+                issues.push(HealthIssue::DuplicateId(idx, item.id));
+            }
+        }
+        issues
+    }
+
+    /// Put right what `issue` found, returning a description of the repair.
+    pub fn repair(&mut self, issue: &HealthIssue, now: DateTime<Local>) -> String {
+        match *issue {
+            HealthIssue::CompleteWithoutDate(idx) => {
+                self.items[idx].complete_date = Some(now);
+                format!("[{}] complete_date set to now", idx)
+            }
+            HealthIssue::NonPositiveRepeat(idx, _) => {
+                self.items[idx].repeat_days = Some(1);
+                format!("[{}] repeat interval set to 1 day", idx)
+            }
+            HealthIssue::NonPositiveHourlyRepeat(idx, _) => {
+                self.items[idx].repeat_spec = Some(RepeatSpec::Hours(1));
+                format!("[{}] repeat interval set to 1 hour", idx)
+            }
+            HealthIssue::EmptyWeekdays(idx) => {
+                self.items[idx].repeat_spec = None;
+                format!("[{}] empty weekday repeat removed", idx)
+            }
+            HealthIssue::ZeroTarget(idx) => {
+                self.items[idx].target = None;
+                self.items[idx].progress = 0;
+                format!("[{}] target of 0 removed", idx)
+            }
+            HealthIssue::DuplicateId(idx, _) => {
+                self.items[idx].id = 0;
+                self.assign_ids();
+                format!("[{}] given new id #{}", idx, self.items[idx].id)
+            }
+        }
+    }
+
+    /// Order `indexes` by `key`. Ties fall back to `created_at` (tasks
+    /// without one last), then position, so the order is total and
+    /// `reverse` flips it exactly. For `SortKey::Due`, tasks without a due
+    /// date go after every dated one; passing `repeats_at` dates repeats
+    /// without a deadline by `next_due` as of then instead.
+    pub fn sort_indexes(&self, indexes: &mut [usize], key: SortKey, reverse: bool, repeats_at: Option<DateTime<Local>>) {
+        indexes.sort_by_key(|idx| {
+            let item = &self.items[*idx];
+            let (priority, due) = match key {
+                SortKey::Priority => (Reverse(item.priority), None),
+                SortKey::Created => (Reverse(None), None),
+                SortKey::Due => (Reverse(None), item.due_date.or_else(|| repeats_at.and_then(|now| item.next_due(now)))),
+            };
+            (priority, due.is_none(), due, item.created_at.is_none(), item.created_at, *idx)
+        });
+        if reverse {
+            indexes.reverse();
+        }
+    }
+
+    /// Split the list into the sections `list` prints: pinned incomplete
+    /// tasks, other incomplete tasks, then completed ones (most recently
+    /// completed first unless `insertion_order`).
+    pub fn sections(&self, now: DateTime<Local>, show_deferred: bool, insertion_order: bool) -> ListSections {
+        let mut sections = ListSections::default();
+        for (idx, item) in self.items.iter().enumerate() {
+            if item.is_deferred(now) && !show_deferred {
+                sections.hidden += 1;
+            } else if item.complete {
+                sections.complete.push(idx);
+            } else if item.pinned {
+                sections.pinned.push(idx);
+            } else {
+                sections.incomplete.push(idx);
+            }
+        }
+
+        // Pressing deadlines first; otherwise storage order is kept.
+        let pressing = |idx: &usize| {
+            let status = self.due_windows.status(&self.items[*idx], now);
+            !status.is_some_and(DueStatus::is_pressing)
+        };
+        sections.pinned.sort_by_key(pressing);
+        sections.incomplete.sort_by_key(pressing);
+
+        if !insertion_order {
+            // Most recently completed first; undated completions sink to the
+            // end, and ties keep storage order (the sort is stable).
+            sections.complete.sort_by_key(|idx| Reverse(self.items[*idx].complete_date));
+        }
+        sections
+    }
+
+    /// The task `focus` is set on, if any.
+    pub fn focused(&self) -> Option<usize> {
+        self.items.iter().position(|item| item.focused)
+    }
+
+    /// Indexes of the tasks a plain `list` shows, top to bottom, with
+    /// `sort` being the configured default sort and direction. Inbox tasks
+    /// are left out, as `list` does.
+    pub fn display_order(&self, now: DateTime<Local>, sort: Option<(SortKey, bool)>) -> Vec<usize> {
+        let mut sections = self.sections(now, false, false);
+        if let Some((key, reverse)) = sort {
+            self.sort_indexes(&mut sections.pinned, key, reverse, None);
+            self.sort_indexes(&mut sections.incomplete, key, reverse, None);
+        }
+        [sections.pinned, sections.incomplete, sections.complete]
+        .concat()
+        .into_iter()
+        .filter(|idx| !self.items[*idx].in_inbox())
+        .collect()
+    }
+
+    pub fn stats(&self, now: DateTime<Local>, weights: &PriorityWeights) -> ListStats {
+        let complete = self.items.iter().filter(|item| item.complete).count();
+        let weight = |item: &TodoItem| u64::from(weights.weight(item.priority));
+        let total_weight: u64 = self.items.iter().map(weight).sum();
+        let done_weight: u64 = self.items.iter().filter(|item| item.complete).map(weight).sum();
+        ListStats {
+            total: self.items.len(),
+            complete,
+            incomplete: self.items.len() - complete,
+            repeating: self.items.iter().filter(|item| item.is_repeating()).count(),
+            overdue: self.items.iter().filter(|item| item.is_repeat_due_today(now)).count(),
+            oldest_incomplete_days: self
+            .items
+            .iter()
+            .filter(|item| !item.complete)
+            .filter_map(|item| item.created_at)
+            .min()
+            .map(|created| (now - created).num_days()),
+            weighted_progress: (total_weight > 0)
+            .then(|| (done_weight as f64 * 1000.0 / total_weight as f64).round() / 10.0),
+        }
+    }
+
+    fn to_ron(&self) -> Result<String> {
+        let pretty = PrettyConfig::new()
+        .separate_tuple_members(true)
+        .enumerate_arrays(true);
+
+        ron::ser::to_string_pretty(self, pretty).with_context(|| "Failed to serialize RON")
+    }
+
+    /// Serialize to JSON. With `computed`, each task also carries derived
+    /// fields (see `ComputedItem`) that are never stored.
+    pub fn to_json(&self, now: DateTime<Local>, computed: bool) -> Result<String> {
+        let json = if computed {
+            let items: Vec<ComputedItem> = self
+            .items
+            .iter()
+            .map(|item| ComputedItem::new(item, now))
+            .collect();
+            serde_json::to_string_pretty(&ComputedList { items })
+        } else {
+            serde_json::to_string_pretty(self)
+        };
+        json.with_context(|| "Failed to serialize JSON")
+    }
+
+    /// Markdown checklist grouped into incomplete and complete sections;
+    /// `parse_markdown_tasks` reads it back.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("## Incomplete\n\n");
+        for item in self.items.iter().filter(|item| !item.complete) {
+            out.push_str(&format!("- [ ] {}\n", item.text));
+        }
+        out.push_str("\n## Complete\n\n");
+        for item in self.items.iter().filter(|item| item.complete) {
+            out.push_str(&format!("- [x] {}\n", item.text));
+        }
+        out
+    }
+
+    /// One todo.txt line per task in storage order: `x <done> <added>` for
+    /// completed tasks, `(A)`-style priorities, `+tag` (or the `@context` a
+    /// tag was imported as) and `due:` / `rec:` extensions;
+    /// `parse_todotxt_tasks` reads it back.
+    pub fn to_todotxt(&self) -> String {
+        let date = |date: DateTime<Local>| date.format("%Y-%m-%d").to_string();
+        let mut out = String::new();
+        for item in &self.items {
+            let mut parts = Vec::new();
+            match (item.complete, item.priority) {
+                (true, _) => {
+                    parts.push("x".to_string());
+                    if let Some(done) = item.complete_date {
+                        parts.push(date(done));
+                    }
+                }
+                (false, Some(priority)) => parts.push(format!("({})", priority.letter())),
+                (false, None) => {}
+            }
+            // A lone date after `x` would be read as the completion date.
+            if let Some(created) = item.created_at.filter(|_| !item.complete || item.complete_date.is_some()) {
+                parts.push(date(created));
+            }
+            parts.push(item.text.clone());
+            for tag in &item.tags {
+                parts.push(if tag.starts_with('@') { tag.clone() } else { format!("+{}", tag) });
+            }
+            if let (true, Some(priority)) = (item.complete, item.priority) {
+                parts.push(format!("pri:{}", priority.letter()));
+            }
+            if let Some(due) = item.due_date {
+                parts.push(format!("due:{}", date(due)));
+            }
+            if let Some(days) = item.repeat_days {
+                parts.push(format!("rec:{}d", days));
+            }
+            out.push_str(&parts.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Bring in imported items. Merging appends items whose text isn't
+    /// already in the list and returns how many were skipped as duplicates;
+    /// replacing swaps the whole list out.
+    pub fn import(&mut self, items: Vec<TodoItem>, replace: bool) -> usize {
+        if replace {
+            self.items = items;
+            self.assign_ids();
+            return 0;
+        }
+
+        let mut skipped = 0;
+        for item in items {
+            if self.items.iter().any(|existing| existing.text == item.text) {
+                skipped += 1;
+            } else {
+                self.items.push(item);
+            }
+        }
+        self.assign_ids();
+        skipped
+    }
+
+    /// Compare with `other` (see `ListDiff`).
+    pub fn diff(&self, other: &TodoList) -> ListDiff {
+        let mut diff = ListDiff::default();
+        let mut unmatched: Vec<usize> = (0..other.items.len()).collect();
+        for (idx, item) in self.items.iter().enumerate() {
+            let found = unmatched
+            .iter()
+            .position(|there| other.items[*there].text.trim() == item.text.trim());
+            let Some(pos) = found else {
+                diff.only_here.push(idx);
+                continue;
+            };
+            let there = unmatched.remove(pos);
+            if !item.same_state(&other.items[there]) {
+                diff.changed.push((idx, there));
+            }
+        }
+        diff.only_there = unmatched;
+        diff
+    }
+
+    /// Indexes of items whose text contains `query` (case-insensitive), best
+    /// first: exact word matches before plain substring matches, then
+    /// shorter texts before longer ones, then list order.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<(bool, usize, usize)> = self
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| {
+            let text = item.text.to_lowercase();
+            if !text.contains(&needle) {
+                return None;
+            }
+            let word_match = text
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word == needle);
+            Some((!word_match, item.text.chars().count(), idx))
+        })
+        .collect();
+
+        hits.sort();
+        hits.into_iter().map(|(_, _, idx)| idx).collect()
+    }
+
+    /// Reset every completed repeat that has come due again and return how
+    /// many did. With `catch_up`, each one's `missed_count` records how many
+    /// occurrences went by undone; without it the count is cleared.
+    pub fn auto_reset_repeating(&mut self, now: DateTime<Local>, catch_up: bool) -> usize {
+        let mut reset = 0;
+        for item in &mut self.items {
+            if item.should_reset(now) {
+                let missed = if catch_up { item.missed_occurrences(now) } else { 0 };
+                item.reset_if_due(now);
+                item.missed_count = missed;
+                reset += 1;
+            }
+        }
+        reset
+    }
+
+    fn add(&mut self, item: TodoItem) {
+        self.items.push(item);
+        self.assign_ids();
+    }
+
+    /// Insert at `position` (clamped to the end of the list) and return
+    /// where the item ended up.
+    pub fn insert_at(&mut self, position: usize, item: TodoItem) -> usize {
+        let position = position.min(self.items.len());
+        self.items.insert(position, item);
+        self.assign_ids();
+        position
+    }
+
+    /// Give every task without an id, or with one an earlier task already
+    /// has (e.g. after an import), the next unused id.
+    pub fn assign_ids(&mut self) {
+        let highest = self.items.iter().map(|item| item.id).max().unwrap_or(0);
+        self.next_id = self.next_id.max(highest + 1);
+        let mut seen = HashSet::new();
+        for item in &mut self.items {
+            if item.id == 0 || !seen.insert(item.id) {
+                item.id = self.next_id;
+                self.next_id += 1;
+                seen.insert(item.id);
+            }
+        }
+    }
+
+    /// How many tasks were added, and how many completions recorded, since
+    /// `since`. Tasks without `created_at` don't count as added.
+    pub fn activity_since(&self, since: DateTime<Local>) -> (usize, usize) {
+        let added = self.items.iter().filter(|item| item.created_at.is_some_and(|at| at >= since)).count();
+        let completed = self
+        .items
+        .iter()
+        .flat_map(|item| &item.completion_history)
+        .filter(|at| **at >= since)
+        .count();
+        (added, completed)
+    }
+
+    /// Storage index of the task with this id.
+    pub fn find_by_id(&self, id: u64) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    /// Where `auto_archive_days` moves old tasks: `<name>.archive.ron`, a
+    /// list of its own (so `--file` can open it).
+    pub fn archive_path_for(list_path: &Path) -> PathBuf {
+        list_path.with_extension("archive.ron")
+    }
+
+    /// Take out the completed, non-repeating tasks finished at least `days`
+    /// days before `now`, in list order.
+    pub fn take_archivable(&mut self, now: DateTime<Local>, days: i64) -> Vec<TodoItem> {
+        let cutoff = now - Duration::days(days);
+        let (old, kept) = std::mem::take(&mut self.items).into_iter().partition(|item: &TodoItem| {
+            item.complete && !item.is_repeating() && item.complete_date.is_some_and(|done| done <= cutoff)
+        });
+        self.items = kept;
+        old
+    }
+
+    /// Tasks in storage order; a task's position here is its index in `list`.
+    pub fn iter(&self) -> std::slice::Iter<'_, TodoItem> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&TodoItem> {
+        self.items.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut TodoItem> {
+        self.items.get_mut(index)
+    }
+}
+
+/// `list[i]` panics on an index past the end, like a slice; use
+/// `TodoList::get` for user-supplied indexes.
+impl Index<usize> for TodoList {
+    type Output = TodoItem;
+
+    fn index(&self, index: usize) -> &TodoItem {
+        &self.items[index]
+    }
+}
+
+impl IndexMut<usize> for TodoList {
+    fn index_mut(&mut self, index: usize) -> &mut TodoItem {
+        &mut self.items[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a TodoList {
+    type Item = &'a TodoItem;
+    type IntoIter = std::slice::Iter<'a, TodoItem>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Parse `- [ ] text` / `- [x] text` checklist lines into tasks. Checked
+/// boxes become complete as of `now`; `##` headers and blank lines are
+/// accepted but only the checkbox decides the state. Returns the tasks and
+/// how many other lines were skipped.
+pub fn parse_markdown_tasks(input: &str, now: DateTime<Local>) -> (Vec<TodoItem>, usize) {
+    let mut items = Vec::new();
+    let mut skipped = 0;
+
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let body = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .map(str::trim_start);
+        let parsed = body.and_then(|body| {
+            if let Some(text) = body.strip_prefix("[ ]") {
+                Some((false, text))
+            } else {
+                body.strip_prefix("[x]")
+                .or_else(|| body.strip_prefix("[X]"))
+                .map(|text| (true, text))
+            }
+        });
+
+        match parsed {
+            Some((done, text)) if !text.trim().is_empty() => {
+                let mut item = TodoItem::new(text.trim().to_string(), None);
+                item.created_at = Some(now);
+                if done {
+                    item.mark_complete(now);
+                }
+                items.push(item);
+            }
+            _ => skipped += 1,
+        }
+    }
+
+    (items, skipped)
+}
+
+/// Parse todo.txt lines into tasks: a leading `x` (then the completion
+/// date) marks them complete, `(A)`/`(B)`/`(C)` set high/medium/low
+/// priority, a date before the text is the creation date, `+project` and
+/// `@context` become tags, and `due:`, `pri:` and `rec:<n>d` are read too.
+/// Returns the tasks and how many lines had no task text.
+pub fn parse_todotxt_tasks(input: &str, now: DateTime<Local>) -> (Vec<TodoItem>, usize) {
+    let day = |word: &str| NaiveDate::parse_from_str(word, "%Y-%m-%d").ok().and_then(local_midnight);
+    let mut items = Vec::new();
+    let mut skipped = 0;
+
+    for line in input.lines() {
+        let mut words = line.split_whitespace().peekable();
+        if words.peek().is_none() {
+            continue;
+        }
+
+        let done = words.next_if_eq(&"x").is_some();
+        let done_at = match done {
+            true => words.next_if(|word| day(word).is_some()).and_then(day),
+            false => None,
+        };
+        let mut priority = words
+        .next_if(|word| word.len() == 3 && word.starts_with('(') && word.ends_with(')'))
+        .and_then(|word| Priority::from_letter(word.chars().nth(1)?));
+        let created = words.next_if(|word| day(word).is_some()).and_then(day);
+
+        let mut text = Vec::new();
+        let mut tags = Vec::new();
+        let mut due = None;
+        let mut repeat = None;
+        for word in words {
+            if let Some(project) = word.strip_prefix('+').filter(|rest| !rest.is_empty()) {
+                tags.push(project.to_string());
+            } else if word.len() > 1 && word.starts_with('@') {
+                tags.push(word.to_string());
+            } else if let Some(date) = word.strip_prefix("due:").and_then(day) {
+                due = Some(date);
+            } else if let Some(level) = word.strip_prefix("pri:").and_then(|rest| rest.chars().next()) {
+                priority = Priority::from_letter(level).or(priority);
+            } else if let Some(days) = word
+            .strip_prefix("rec:")
+            .and_then(|rest| rest.strip_suffix('d'))
+            .and_then(|days| days.parse::<i64>().ok())
+            .filter(|days| *days >= 1)
+            {
+                repeat = Some(days);
+            } else {
+                text.push(word);
+            }
+        }
+        if text.is_empty() {
+            skipped += 1;
+            continue;
+        }
+
+        let mut item = TodoItem::new(text.join(" "), repeat);
+        item.add_tags(&tags);
+        item.priority = priority;
+        item.due_date = due;
+        item.created_at = created.or(Some(now));
+        if done {
+            item.mark_complete(done_at.unwrap_or(now));
+        }
+        items.push(item);
+    }
+
+    (items, skipped)
+}
+
+/// Colors are used only on a terminal, and never with `--no-color` or when
+/// the `NO_COLOR` environment variable is set.
+fn color_enabled(no_color: bool) -> bool {
+    !no_color && env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+/// `text` with every occurrence of `from` replaced by `to`, or `None` if
+/// it doesn't occur. With `ignore_case`, occurrences are matched a
+/// character at a time so text whose case changes length is still safe.
+pub fn replace_text(text: &str, from: &str, to: &str, ignore_case: bool) -> Option<String> {
+    if from.is_empty() {
+        return None;
+    }
+    if !ignore_case {
+        return text.contains(from).then(|| text.replace(from, to));
+    }
+
+    let needle = from.to_lowercase();
+    let width = from.chars().count();
+    let mut out = String::new();
+    let mut found = false;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest.char_indices().nth(width).map_or(rest.len(), |(idx, _)| idx);
+        if rest[..end].to_lowercase() == needle {
+            out.push_str(to);
+            rest = &rest[end..];
+            found = true;
+        } else {
+            let next = rest.chars().next().unwrap();
+            out.push(next);
+            rest = &rest[next.len_utf8()..];
+        }
+    }
+    found.then_some(out)
+}
+
+/// Wrap each case-insensitive occurrence of `query` in bold+underline.
+fn highlight(text: &str, query: &str) -> String {
+    let lower = text.to_lowercase();
+    let needle = query.trim().to_lowercase();
+    // Lowercasing can change byte lengths for some scripts; don't risk
+    // slicing mid-character, just skip highlighting.
+    if needle.is_empty() || lower.len() != text.len() {
+        return text.to_string();
+    }
+
+    let mut out = String::new();
+    let mut rest = 0;
+    for (start, _) in lower.match_indices(&needle) {
+        if start < rest {
+            continue;
+        }
+        let end = start + needle.len();
+        out.push_str(&text[rest..start]);
+        out.push_str("\x1B[1;4m");
+        out.push_str(&text[start..end]);
+        out.push_str("\x1B[0m");
+        rest = end;
+    }
+    out.push_str(&text[rest..]);
+    out
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_string()
+}
+
+/// Run git in the data file's directory, returning whether it succeeded.
+fn git(dir: &Path, args: &[&str]) -> Result<bool> {
+    let output = Command::new("git")
+    .arg("-C")
+    .arg(dir)
+    .args(args)
+    .output()
+    .with_context(|| "Failed to run git")?;
+    Ok(output.status.success())
+}
+
+/// Snapshots kept per list; the oldest are dropped first.
+pub const MAX_SNAPSHOTS: usize = 20;
+/// Upper bound on the snapshot file size, so big lists don't keep 20 copies.
+pub const MAX_SNAPSHOT_BYTES: usize = 1024 * 1024;
+
+/// The list as it was just before a change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Local>,
+    /// What changed it, e.g. "delete tasks".
+    pub command: String,
+    pub list: TodoList,
+}
+
+/// Snapshots stored next to the list in `<name>.snapshots.ron`, oldest first.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SnapshotLog {
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl SnapshotLog {
+    pub fn path_for(list_path: &Path) -> PathBuf {
+        list_path.with_extension("snapshots.ron")
+    }
+
+    /// Snapshots hold whole lists, so they are encrypted like the list.
+    fn load(path: &Path, passphrase: Option<&Passphrase>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(SnapshotLog::default());
+        }
+
+        let contents = read_data(path, passphrase)
+        .with_context(|| format!("Failed to read snapshots: {}", path.display()))?;
+        ron::from_str(&contents)
+        .map_err(TodoError::Parse)
+        .with_context(|| format!("Failed to parse snapshots: {}", path.display()))
+    }
+
+    fn save(&self, path: &Path, passphrase: Option<&Passphrase>) -> Result<()> {
+        let ron_string = self.to_ron()?;
+        let data = match passphrase {
+            Some(passphrase) => encrypt_data(ron_string.as_bytes(), passphrase)?,
+            None => ron_string.into_bytes(),
+        };
+        fs::write(path, data)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to write snapshots: {}", path.display()))
+    }
+
+    fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::new()).with_context(|| "Failed to serialize snapshots")
+    }
+
+    /// Add a snapshot, then drop the oldest until at most `MAX_SNAPSHOTS`
+    /// remain and the log fits in `MAX_SNAPSHOT_BYTES` (always keeping the
+    /// newest).
+    pub fn push(&mut self, snapshot: Snapshot) -> Result<()> {
+        self.snapshots.push(snapshot);
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.drain(..self.snapshots.len() - MAX_SNAPSHOTS);
+        }
+        while self.snapshots.len() > 1 && self.to_ron()?.len() > MAX_SNAPSHOT_BYTES {
+            self.snapshots.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Snapshot `n` counting back from the newest (1 = newest).
+    pub fn nth_newest(&self, n: usize) -> Option<&Snapshot> {
+        n.checked_sub(1)
+        .and_then(|back| self.snapshots.len().checked_sub(back + 1))
+        .map(|idx| &self.snapshots[idx])
+    }
+}
+
+/// When `list` was last run on a data file, for `list --new`, and the last
+/// command that changed it, for `replay-last`. Kept in a file of its own so
+/// looking at the list never rewrites (or, with `--git`, dirties) the data
+/// file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ViewState {
+    #[serde(default)]
+    pub last_viewed: Option<DateTime<Local>>,
+    /// Arguments of the last command that changed the list, as typed.
+    #[serde(default)]
+    pub last_command: Option<Vec<String>>,
+}
+
+impl ViewState {
+    pub fn path_for(list_path: &Path) -> PathBuf {
+        list_path.with_extension("state.ron")
+    }
+
+    /// Store `now` as the latest view and return the one before it. Best
+    /// effort: an unreadable or unwritable state file only costs `--new`
+    /// its memory, never the listing.
+    fn record(path: &Path, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut state = ViewState::load(path);
+        let previous = state.last_viewed.replace(now);
+        state.save(path);
+        previous
+    }
+
+    /// Remember `args` for `replay-last`. Skipped for encrypted lists, whose
+    /// task text shouldn't end up in a plain file next to them.
+    fn remember_command(list_path: &Path, args: Vec<String>) {
+        if fs::read(list_path).is_ok_and(|data| is_encrypted(&data)) {
+            return;
+        }
+        let path = ViewState::path_for(list_path);
+        let mut state = ViewState::load(&path);
+        state.last_command = Some(args);
+        state.save(&path);
+    }
+
+    fn load(path: &Path) -> ViewState {
+        fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| ron::from_str(&contents).ok())
+        .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) {
+        if let Ok(ron_string) = ron::ser::to_string_pretty(self, PrettyConfig::new()) {
+            let _ = fs::write(path, ron_string);
+        }
+    }
+}
+
+/// Start of an encrypted data file; a salt, a nonce and the ciphertext
+/// follow.
+const ENCRYPTED_HEADER: &[u8] = b"todoster encrypted v1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Passphrase for encrypted data files, from `TODOSTER_KEY` or `--encrypt`.
+/// Kept out of `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Passphrase(passphrase.into())
+    }
+
+    /// Derive the XChaCha20-Poly1305 key for `salt` with Argon2id.
+    fn cipher(&self, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+        .hash_password_into(self.0.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow::anyhow!("Failed to derive encryption key: {}", err))?;
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_HEADER)
+}
+
+/// Encrypt `plain` under a fresh salt and nonce, header first.
+pub fn encrypt_data(plain: &[u8], passphrase: &Passphrase) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = passphrase
+    .cipher(&salt)?
+    .encrypt(&nonce, plain)
+    .map_err(|_| anyhow::anyhow!("Failed to encrypt data"))?;
+
+    Ok([ENCRYPTED_HEADER, &salt, &nonce, &ciphertext].concat())
+}
+
+/// Reverse `encrypt_data`. A wrong passphrase and a tampered or truncated
+/// file both fail authentication, as `TodoError::Decryption`.
+pub fn decrypt_data(data: &[u8], passphrase: &Passphrase) -> Result<Vec<u8>> {
+    let body = data.strip_prefix(ENCRYPTED_HEADER).ok_or(TodoError::Decryption)?;
+    if body.len() < SALT_LEN + NONCE_LEN {
+        return Err(TodoError::Decryption.into());
+    }
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    passphrase
+    .cipher(salt)?
+    .decrypt(XNonce::from_slice(nonce), ciphertext)
+    .map_err(|_| TodoError::Decryption.into())
+}
+
+/// Read a data (or snapshot) file as text, decrypting it if it is
+/// encrypted. Plain files read the same with or without a passphrase.
+fn read_data(path: &Path, passphrase: Option<&Passphrase>) -> Result<String> {
+    let data = fs::read(path)
+    .map_err(TodoError::Io)
+    .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    if !is_encrypted(&data) {
+        return String::from_utf8(data).with_context(|| format!("{} is not valid UTF-8", path.display()));
+    }
+
+    let passphrase = passphrase.ok_or_else(|| {
+        invalid_input(format!(
+            "{} is encrypted; set TODOSTER_KEY or pass --encrypt to enter the passphrase.",
+            path.display()
+        ))
+    })?;
+    let plain = decrypt_data(&data, passphrase).with_context(|| format!("Cannot open {}", path.display()))?;
+    String::from_utf8(plain).with_context(|| format!("{} is not valid UTF-8", path.display()))
+}
+
+/// The passphrase for this run: `TODOSTER_KEY` if set, otherwise asked for
+/// on the terminal with `--encrypt` (twice when encrypting a file for the
+/// first time, to catch typos).
+fn resolve_passphrase(encrypt: bool, path: &Path) -> Result<Option<Passphrase>> {
+    if let Some(key) = env::var("TODOSTER_KEY").ok().filter(|key| !key.is_empty()) {
+        return Ok(Some(Passphrase::new(key)));
+    }
+    if !encrypt {
+        return Ok(None);
+    }
+
+    let prompt = |text: &str| {
+        rpassword::prompt_password(text)
+        .map_err(TodoError::Io)
+        .with_context(|| "Failed to read passphrase")
+    };
+    let passphrase = prompt("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(invalid_input("The passphrase can't be empty."));
+    }
+    let already_encrypted = fs::read(path).is_ok_and(|data| is_encrypted(&data));
+    if !already_encrypted && prompt("Repeat passphrase: ")? != passphrase {
+        return Err(invalid_input("Passphrases don't match."));
+    }
+    Ok(Some(Passphrase::new(passphrase)))
+}
+
+/// Move tasks completed at least `days` ago from the stored list at `path`
+/// into its archive file.
+fn auto_archive(path: &Path, passphrase: Option<&Passphrase>, now: DateTime<Local>, days: i64) -> Result<()> {
+    let path = path.to_path_buf();
+    let mut list = TodoList::load(&path, passphrase)?;
+    let old = list.take_archivable(now, days);
+    if old.is_empty() {
+        return Ok(());
+    }
+    let archive_path = TodoList::archive_path_for(&path);
+    let mut archive = TodoList::load(&archive_path, passphrase)?;
+    archive.compact = list.compact;
+    let count = old.len();
+    archive.items.extend(old);
+    archive.save(&archive_path)?;
+    list.save(&path)?;
+    eprintln!(
+        "Archived {} task(s) completed over {} day(s) ago to {}.",
+        count,
+        days,
+        archive_path.display()
+    );
+    Ok(())
+}
+
+/// Record `before` as a snapshot if the command changed the stored list.
+fn record_snapshot(
+    path: &Path,
+    before: TodoList,
+    command: &str,
+    now: DateTime<Local>,
+    catch_up: bool,
+) -> Result<()> {
+    // `before` was auto-reset in memory; do the same so that alone isn't a change.
+    let passphrase = before.passphrase.clone();
+    let mut after = TodoList::load(&path.to_path_buf(), passphrase.as_ref())?;
+    after.auto_reset_repeating(now, catch_up);
+    if before.to_ron()? == after.to_ron()? {
+        return Ok(());
+    }
+
+    let log_path = SnapshotLog::path_for(path);
+    let mut log = SnapshotLog::load(&log_path, passphrase.as_ref())?;
+    log.push(Snapshot {
+        taken_at: now,
+        command: command.trim_start_matches("todoster: ").to_string(),
+        list: before,
+    })?;
+    log.save(&log_path, passphrase.as_ref())
+}
+
+/// Commit the data file if it changed. Silently does nothing when the file
+/// isn't inside a git work tree (or git isn't installed).
+fn git_commit(path: &Path, message: &str) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Some(file) = path.file_name().and_then(|f| f.to_str()) else {
+        return Ok(());
+    };
+
+    if !git(dir, &["rev-parse", "--is-inside-work-tree"]).unwrap_or(false) {
+        return Ok(());
+    }
+
+    // Nothing staged means the command didn't actually change the file.
+    if !git(dir, &["add", "--", file])? || git(dir, &["diff", "--cached", "--quiet", "--", file])? {
+        return Ok(());
+    }
+
+    if !git(dir, &["commit", "--quiet", "-m", message, "--", file])? {
+        eprintln!("Warning: git commit of {} failed.", path.display());
+    }
+    Ok(())
+}
+
+fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config").join("todoster")
+}
+
+fn data_dir() -> PathBuf {
+    base_dir("XDG_DATA_HOME", ".local/share").join("todoster")
+}
+
+/// `$xdg_var`, else `$HOME/<under_home>`. With neither set (bare CI
+/// containers) this is the current directory, which is easy to miss, so
+/// say so once per run.
+fn base_dir(xdg_var: &str, under_home: &str) -> PathBuf {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+
+    env::var(xdg_var)
+    .map(PathBuf::from)
+    .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(under_home)))
+    .unwrap_or_else(|_| {
+        WARNED.call_once(|| {
+            eprintln!(
+                "Warning: neither HOME nor {} is set; using ./todoster in the current directory \
+                 (pass --file to choose the data file).",
+                xdg_var
+            );
+        });
+        PathBuf::from(".")
+    })
+}
+
+/// A list's name: its file name without the extension.
+fn list_name(path: &Path) -> &str {
+    path.file_stem().and_then(|s| s.to_str()).unwrap_or("todos")
+}
+
+/// Check a data file without loading it the usual way (which would
+/// migrate and auto-reset it), print what was found and fail if anything
+/// still needs attention. Only writes with `fix`, after copying the file to
+/// `<file>.bak`; returns whether it did.
+fn doctor(path: &Path, fix: bool, passphrase: Option<&Passphrase>, now: DateTime<Local>) -> Result<bool> {
+    println!("Checking {}", path.display());
+    let mut problems = Vec::new();
+
+    // The directory may not exist yet either; then whichever ancestor
+    // does is where `save` would have to create it.
+    let dir = path
+    .ancestors()
+    .skip(1)
+    .find(|dir| dir.is_dir())
+    .unwrap_or(Path::new("."));
+    let read_only = |p: &Path| fs::metadata(p).is_ok_and(|meta| meta.permissions().readonly());
+    if read_only(dir) {
+        problems.push(format!("storage directory {} is not writable", dir.display()));
+    } else if path.exists() && read_only(path) {
+        problems.push("data file is read-only".to_string());
+    } else {
+        println!("  storage directory {} is writable", dir.display());
+    }
+    let writable = problems.is_empty();
+
+    let mut repaired = false;
+    if !path.exists() {
+        println!("  no data file yet (it is created on the first change)");
+    } else {
+        match read_data(path, passphrase) {
+            Ok(contents) => {
+                let fix = fix && writable;
+                repaired = doctor_contents(path, &contents, fix, passphrase, now, &mut problems)?;
+            }
+            Err(err) => problems.push(format!("cannot be read: {:#}", err)),
+        }
+    }
+
+    if problems.is_empty() {
+        println!("No problems found.");
+        return Ok(repaired);
+    }
+    for problem in &problems {
+        println!("  problem: {}", problem);
+    }
+    Err(anyhow::anyhow!("doctor found {} problem(s)", problems.len()))
+}
+
+/// The part of `doctor` that looks inside the file: version, parsing and
+/// task state. Repairs (and saves) with `fix`; returns whether it did.
+fn doctor_contents(
+    path: &Path,
+    contents: &str,
+    fix: bool,
+    passphrase: Option<&Passphrase>,
+    now: DateTime<Local>,
+    problems: &mut Vec<String>,
+) -> Result<bool> {
+    let newer = ron::from_str::<VersionProbe>(contents)
+    .ok()
+    .map(|probe| probe.version)
+    .filter(|version| *version > SCHEMA_VERSION);
+    if let Some(version) = newer {
+        problems.push(TodoError::UnsupportedVersion(version).to_string());
+    }
+
+    let mut list = match ron::from_str::<TodoList>(contents) {
+        Ok(list) => list,
+        Err(err) => {
+            problems.push(format!("does not parse: {}", err));
+            return Ok(false);
+        }
+    };
+    println!("  parses: {} task(s), schema version {}", list.items.len(), list.version);
+
+    let issues = list.health_issues();
+    if !fix || newer.is_some() || issues.is_empty() {
+        problems.extend(issues.iter().map(HealthIssue::to_string));
+        return Ok(false);
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::copy(path, &backup)
+    .map_err(TodoError::Io)
+    .with_context(|| format!("Failed to back up {}", path.display()))?;
+    println!("  backed up to {}", Path::new(&backup).display());
+
+    for issue in &issues {
+        println!("  fixed: {}", list.repair(issue, now));
+    }
+    list.passphrase = passphrase.cloned();
+    list.save(&path.to_path_buf())?;
+    Ok(true)
+}
+
+/// Open the data file in `$VISUAL` or `$EDITOR` (else `vi`, or `notepad` on
+/// Windows) and check it still loads afterwards. A broken edit can be
+/// reopened to fix; otherwise the file goes back to how it was and the
+/// edit is kept in `<file>.rejected`. Returns whether the file changed.
+fn edit_file(path: &Path) -> Result<bool> {
+    if fs::read(path).is_ok_and(|data| is_encrypted(&data)) {
+        return Err(invalid_input(format!(
+            "{} is encrypted; edit-file only works on plain files.",
+            path.display()
+        )));
+    }
+    if !path.exists() {
+        TodoList::default().save(&path.to_path_buf())?;
+    }
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let original = read(path)?;
+
+    loop {
+        run_editor(path)?;
+        let edited = read(path)?;
+        let err = match TodoList::parse(&edited) {
+            Ok(list) if edited == original => {
+                println!("No changes ({} task(s)).", list.items.len());
+                return Ok(false);
+            }
+            Ok(list) => {
+                println!("Saved: {} task(s), and the file loads fine.", list.items.len());
+                return Ok(true);
+            }
+            Err(err) => err,
+        };
+
+        if std::io::stdin().is_terminal() {
+            eprintln!("The edited file doesn't load: {:#}", err);
+            print!("Reopen the editor to fix it? [Y/n] ");
+            std::io::stdout().flush().map_err(TodoError::Io)?;
+            let mut answer = String::new();
+            std::io::stdin()
+            .read_line(&mut answer)
+            .map_err(TodoError::Io)
+            .with_context(|| "Failed to read answer")?;
+            if matches!(answer.trim(), "" | "y" | "Y" | "yes") {
+                continue;
+            }
+        }
+
+        let mut rejected = path.as_os_str().to_owned();
+        rejected.push(".rejected");
+        let rejected = PathBuf::from(rejected);
+        fs::write(&rejected, &edited)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to write {}", rejected.display()))?;
+        fs::write(path, &original)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to restore {}", path.display()))?;
+        return Err(err.context(format!(
+            "Edit undone, {} is as it was; your version is in {}",
+            path.display(),
+            rejected.display()
+        )));
+    }
+}
+
+/// Run the user's editor on `path` and wait for it to exit. The editor
+/// setting may carry arguments, e.g. "code --wait".
+fn run_editor(path: &Path) -> Result<()> {
+    let fallback = if cfg!(windows) { "notepad" } else { "vi" };
+    let editor = env::var("VISUAL")
+    .or_else(|_| env::var("EDITOR"))
+    .ok()
+    .filter(|editor| !editor.trim().is_empty())
+    .unwrap_or_else(|| fallback.to_string());
+    let mut words = editor.split_whitespace();
+    let status = Command::new(words.next().unwrap_or(fallback))
+    .args(words)
+    .arg(path)
+    .status()
+    .map_err(TodoError::Io)
+    .with_context(|| format!("Failed to start editor `{}`", editor))?;
+    if !status.success() {
+        return Err(invalid_input(format!("Editor `{}` failed ({}).", editor, status)));
+    }
+    Ok(())
+}
+
+/// Concatenate several list files into one list for display, prefixing
+/// each task's text with the name of the list it came from. The files are
+/// only read, never migrated on disk.
+fn load_merged(paths: &[PathBuf], passphrase: Option<&Passphrase>) -> Result<TodoList> {
+    let mut merged = TodoList::default();
+    for path in paths {
+        if !path.exists() {
+            return Err(invalid_input(format!("No such list file: {}", path.display())));
+        }
+        for mut item in TodoList::read_only(path, passphrase)?.items {
+            item.text = format!("{}: {}", list_name(path), item.text);
+            merged.items.push(item);
+        }
+    }
+    Ok(merged)
+}
+
+fn default_file_path() -> PathBuf {
+    data_dir().join("todos.ron")
+}
+
+/// Where the data file lived before it moved out of the config directory.
+fn legacy_file_path() -> PathBuf {
+    config_dir().join("todos.ron")
+}
+
+/// Move the data file from `old` to `new` if only the old one exists.
+/// Returns true if a file was moved.
+pub fn migrate_data_file(old: &Path, new: &Path) -> Result<bool> {
+    if new.exists() || !old.exists() {
+        return Ok(false);
+    }
+
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    // A plain rename fails across filesystems; fall back to copy + remove.
+    if fs::rename(old, new).is_err() {
+        fs::copy(old, new)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to copy {} to {}", old.display(), new.display()))?;
+        fs::remove_file(old)
+        .map_err(TodoError::Io)
+        .with_context(|| format!("Failed to remove {}", old.display()))?;
+    }
+    Ok(true)
+}
+
+fn config_file_path() -> PathBuf {
+    config_dir().join("config.ron")
+}
+
+fn templates_file_path() -> PathBuf {
+    config_dir().join("templates.ron")
+}
+
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Local>> {
+    local_at(date, NaiveTime::MIN)
+}
+
+fn local_at(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+/// Parse a reminder lead time such as "30m", "2h", "1d" or "1w" into
+/// minutes; it must be at least a minute.
+pub fn parse_lead_time(spec: &str) -> Result<i64> {
+    let spec = spec.trim().to_ascii_lowercase();
+    let minutes = spec
+    .char_indices()
+    .last()
+    .and_then(|(at, unit)| {
+        let per_unit = match unit {
+            'm' => 1,
+            'h' => 60,
+            'd' => 24 * 60,
+            'w' => 7 * 24 * 60,
+            _ => return None,
+        };
+        spec[..at].trim().parse::<i64>().ok()?.checked_mul(per_unit)
+    })
+    .ok_or_else(|| invalid_input(format!("Unrecognised lead time: {} (expected e.g. 30m, 2h, 1d or 1w)", spec)))?;
+    if minutes < 1 {
+        return Err(invalid_input("Lead time must be at least 1 minute."));
+    }
+    Ok(minutes)
+}
+
+/// "2h", "1h 30m", "45m" or "1d 3h": how long until a reminder's deadline.
+fn countdown(left: Duration) -> String {
+    let (days, hours, minutes) = (left.num_days(), left.num_hours() % 24, left.num_minutes() % 60);
+    match (days, hours, minutes) {
+        (0, 0, _) => format!("{}m", minutes),
+        (0, _, 0) => format!("{}h", hours),
+        (0, _, _) => format!("{}h {}m", hours, minutes),
+        (_, 0, _) => format!("{}d", days),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// Parse a time of day given as `HH:MM` (24-hour clock).
+pub fn parse_time_of_day(spec: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(spec.trim(), "%H:%M")
+    .map_err(|_| invalid_input(format!("Unrecognised time: {} (expected HH:MM, e.g. 08:00)", spec)))
+}
+
+/// Repeat intervals must be at least one day; zero or negative intervals
+/// would make a task due again the moment it is completed.
+pub fn validate_repeat_days(days: i64) -> Result<i64> {
+    if days < 1 {
+        return Err(invalid_input(format!(
+            "Repeat interval must be at least 1 day (got {}).",
+            days
+        )));
+    }
+    Ok(days)
+}
+
+/// Task text can't be blank and, with a `max_chars` limit, must fit it:
+/// longer text is refused unless `truncate`, which cuts it to the limit
+/// ending in "…". Returns the text and whether it was cut.
+pub fn validate_task_text(text: String, max_chars: Option<usize>, truncate: bool) -> Result<(String, bool)> {
+    if text.trim().is_empty() {
+        return Err(invalid_input("Task text can't be empty."));
+    }
+
+    let length = text.chars().count();
+    match max_chars {
+        Some(max) if length > max && truncate => {
+            let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+            cut.truncate(cut.trim_end().len());
+            cut.push('…');
+            Ok((cut, true))
+        }
+        Some(max) if length > max => Err(invalid_input(format!(
+            "Task text is {} characters; the limit is {} (pass --truncate to shorten it).",
+            length, max
+        ))),
+        _ => Ok((text, false)),
+    }
+}
+
+/// Check a task link is a plain http(s) URL, returning it trimmed.
+pub fn validate_url(url: &str) -> Result<String> {
+    let url = url.trim();
+    let lower = url.to_ascii_lowercase();
+    let rest = lower.strip_prefix("https://").or_else(|| lower.strip_prefix("http://"));
+    match rest {
+        Some(rest) if !rest.is_empty() && !url.contains(char::is_whitespace) => Ok(url.to_string()),
+        _ => Err(invalid_input(format!(
+            "\"{}\" is not a link; it must start with http:// or https://.",
+            url
+        ))),
+    }
+}
+
+/// The system clipboard's text, for `add --from-clipboard`.
+#[cfg(feature = "clipboard")]
+fn clipboard_text() -> Result<String> {
+    arboard::Clipboard::new()
+    .and_then(|mut clipboard| clipboard.get_text())
+    .with_context(|| "Failed to read the clipboard")
+}
+
+/// Put `text` on the system clipboard, for `export --to-clipboard`.
+#[cfg(feature = "clipboard")]
+fn set_clipboard_text(text: String) -> Result<()> {
+    arboard::Clipboard::new()
+    .and_then(|mut clipboard| clipboard.set_text(text))
+    .with_context(|| "Failed to write to the clipboard")
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn clipboard_text() -> Result<String> {
+    Err(no_clipboard())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn set_clipboard_text(_text: String) -> Result<()> {
+    Err(no_clipboard())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn no_clipboard() -> anyhow::Error {
+    invalid_input("This build has no clipboard support; reinstall with `--features clipboard`.")
+}
+
+/// Launch `url` in the default browser.
+fn open_url(url: &str) -> Result<()> {
+    open::that(url).with_context(|| format!("Failed to open {}", url))
+}
+
+/// Parse a comma-separated list of weekday names, e.g. "mon,thu".
+pub fn parse_weekdays(spec: &str) -> Result<Vec<Weekday>> {
+    let mut days = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let day = part
+        .parse::<Weekday>()
+        .map_err(|_| invalid_input(format!("Unrecognised weekday \"{}\"", part)))?;
+        if !days.contains(&day) {
+            days.push(day);
+        }
+    }
+
+    if days.is_empty() {
+        return Err(invalid_input("Give at least one weekday, e.g. \"mon,thu\"."));
+    }
+    Ok(days)
+}
+
+/// What `parse_quick_add` found in the text given to `add`.
+#[derive(Debug, Default, PartialEq)]
+pub struct QuickAdd {
+    /// The text with the recognised tokens taken out.
+    pub text: String,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    pub due: Option<DateTime<Local>>,
+}
+
+/// Pull Todoist-style tokens out of task text: `!high` / `!medium` /
+/// `!low`, `#tag` and `due:<date>` (anything `parse_date_spec` accepts).
+/// Other words, including a bare `!` or `#`, stay in the text.
+pub fn parse_quick_add(text: &str, now: DateTime<Local>) -> Result<QuickAdd> {
+    let mut quick = QuickAdd::default();
+    let mut words = Vec::new();
+    let mut found = false;
+
+    for word in text.split_whitespace() {
+        if let Some(priority) = word.strip_prefix('!').and_then(|p| Priority::from_str(p, true).ok()) {
+            quick.priority = Some(priority);
+        } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            quick.tags.push(tag.to_string());
+        } else if let Some(spec) = word.strip_prefix("due:").filter(|spec| !spec.is_empty()) {
+            quick.due = Some(parse_date_spec(spec, now)?);
+        } else {
+            words.push(word);
+            continue;
+        }
+        found = true;
+    }
+
+    // Leave text without any tokens exactly as typed.
+    quick.text = if found { words.join(" ") } else { text.to_string() };
+    Ok(quick)
+}
+
+/// Parse a user-supplied date: `today`, `tomorrow`, `+3d`/`2w`, a weekday
+/// name (next occurrence), `YYYY-MM-DD`, or a full RFC3339 timestamp.
+/// Anything without a time of day resolves to local midnight.
+pub fn parse_date_spec(spec: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let s = spec.trim().to_lowercase();
+    let today = now.date_naive();
+
+    let date = match s.as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        _ => None,
+    };
+
+    let date = date.or_else(|| {
+        let offset = s.strip_prefix('+').unwrap_or(&s);
+        let (at, unit) = offset.char_indices().last()?;
+        let n = offset[..at].parse::<i64>().ok()?;
+        let step = match unit {
+            'd' => Duration::try_days(n)?,
+            'w' => Duration::try_weeks(n)?,
+            _ => return None,
+        };
+        today.checked_add_signed(step)
+    });
+
+    let date = date.or_else(|| {
+        let weekday = s.parse::<Weekday>().ok()?;
+        let ahead = (7 + weekday.num_days_from_monday() as i64
+            - today.weekday().num_days_from_monday() as i64)
+            % 7;
+        let ahead = if ahead == 0 { 7 } else { ahead };
+        Some(today + Duration::days(ahead))
+    });
+
+    let date = date.or_else(|| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok());
+
+    if let Some(date) = date {
+        return local_midnight(date)
+        .ok_or_else(|| invalid_input(format!("Date does not exist in local time: {}", spec)));
+    }
+
+    DateTime::parse_from_rfc3339(spec.trim())
+    .map(|dt| dt.with_timezone(&Local))
+    .map_err(|_| {
+        invalid_input(format!(
+            "Unrecognised date \"{}\" (try today, tomorrow, +3d, 2w, friday or YYYY-MM-DD)",
+            spec
+        ))
+    })
+}
+
+/// Options that change what `print_list` shows.
+#[derive(Default)]
+pub struct ListOptions {
+    pub show_deferred: bool,
+    /// Prefix due repeating tasks with `!` (used when notifications fail).
+    pub mark_due: bool,
+    pub week_start: WeekStart,
+    /// Keep completed tasks in storage order rather than newest first.
+    pub insertion_order: bool,
+    /// Colors to apply; `None` prints plain text.
+    pub theme: Option<Theme>,
+    pub repeat_display: RepeatDisplay,
+    pub group_by: GroupBy,
+    /// Order for the incomplete (and pinned) tasks; `None` keeps storage order.
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+    /// Date deadline-less repeats by their next due for `SortKey::Due`.
+    pub sort_with_repeats: bool,
+    /// Only tasks created after this (`list --new`).
+    pub new_since: Option<DateTime<Local>>,
+    /// Only incomplete tasks due on or before this day (`list --due-before`).
+    pub due_before: Option<NaiveDate>,
+    /// Restrict output to ASCII (`--ascii`).
+    pub ascii: bool,
+    pub due_windows: DueWindows,
+    /// Parts of each task line to show, in order; empty shows them all.
+    pub columns: Vec<Column>,
+    /// Timestamps instead of countdowns (`--iso-dates`).
+    pub iso_dates: bool,
+    /// Format for dates written out in full, such as a repeat's end;
+    /// `None` uses `DEFAULT_DATE_FORMAT`.
+    pub date_format: Option<String>,
+    /// Every task in one storage-order list (`list --flat`).
+    pub flat: bool,
+    /// Include inbox tasks, which are otherwise left out (`--show-inbox`).
+    pub show_inbox: bool,
+    /// Start each line with a priority bullet, and explain them below the
+    /// list unless `legend` is off.
+    pub priority_dots: bool,
+    pub legend: bool,
+    /// Color the priority bullets (otherwise they're plain characters).
+    pub color: bool,
+    /// Width to wrap task lines to; `None` leaves them whole.
+    pub wrap: Option<usize>,
+    /// Just the task lines, each ended by a NUL byte (`--print0`).
+    pub print0: bool,
+}
+
+impl ListOptions {
+    fn date_format(&self) -> &str {
+        self.date_format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT)
+    }
+
+    fn header(&self, title: &str) -> String {
+        let title = heading(title, self.ascii);
+        match self.theme.and_then(|theme| theme.header) {
+            Some(color) => color.paint(&title),
+            None => title,
+        }
+    }
+
+    fn shows(&self, column: Column) -> bool {
+        self.columns.is_empty() || self.columns.contains(&column)
+    }
+
+    /// The parts of a task line that `columns` asks for, space-separated.
+    /// `parts` holds every column in default order; empty ones are skipped.
+    fn columns(&self, parts: &[(Column, String)]) -> String {
+        let mut shown: Vec<&(Column, String)> = parts
+        .iter()
+        .filter(|(column, text)| !text.is_empty() && self.shows(*column))
+        .collect();
+        shown.sort_by_key(|(column, _)| self.columns.iter().position(|wanted| wanted == column));
+        shown
+        .iter()
+        .map(|(_, text)| text.strip_prefix(' ').unwrap_or(text))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// `line` as printed: unchanged, or with non-ASCII characters replaced.
+    fn output(&self, line: String) -> String {
+        if self.ascii { ascii_only(&line) } else { line }
+    }
+
+    /// A task line as printed, wrapped to `wrap` with continuation lines
+    /// lined up under the task text: past `lead` (bullet and markers), the
+    /// index and, with `--flat`, the "[x] " in front.
+    fn task_output(&self, idx: usize, lead: &str, line: String) -> String {
+        let line = self.output(line);
+        let Some(width) = self.wrap else {
+            return line;
+        };
+        let start = if self.flat { 4 } else { 0 };
+        let mut indent = start + display_width(&self.output(lead.to_string()));
+        if self.columns.first().is_none_or(|column| *column == Column::Index) {
+            indent += format!("[{}] ", idx).len();
+        }
+        wrap_line(&line, width, start, indent)
+    }
+
+    /// The priority bullet and a space, when `priority_dots` is on.
+    fn dot(&self, priority: Option<Priority>) -> String {
+        match self.priority_dots {
+            true => format!("{} ", priority_dot(priority, self.color, self.ascii)),
+            false => String::new(),
+        }
+    }
+
+    fn index(&self, idx: usize) -> String {
+        let label = format!("[{}]", idx);
+        match self.theme.and_then(|theme| theme.accent) {
+            Some(color) => color.paint(&label),
+            None => label,
+        }
+    }
+}
+
+/// Send a desktop notification for each repeating task that is due today or
+/// overdue. Returns false if any notification could not be delivered (e.g.
+/// on a headless machine), so the caller can fall back to marking the list.
+fn notify_due(list: &TodoList, now: DateTime<Local>) -> bool {
+    let mut delivered = true;
+    for item in list.items.iter().filter(|item| item.is_repeat_due_today(now)) {
+        let sent = notify_rust::Notification::new()
+        .summary("Todoster: task due")
+        .body(&item.text)
+        .appname("todoster")
+        .show();
+        if sent.is_err() {
+            delivered = false;
+        }
+    }
+    delivered
+}
+
+fn incomplete_line(idx: usize, item: &TodoItem, now: DateTime<Local>, opts: &ListOptions) -> String {
+    let repeat_info = match repeat_label(item, opts.week_start) {
+        Some(label) => format!("(Repeat: {}{})", label, repeat_limit_info(item, opts.date_format())),
+        None => String::new(),
+    };
+
+    let marker = if opts.mark_due && item.is_repeat_due_today(now) { "! " } else { "" };
+    let pin = match (item.pinned, opts.ascii) {
+        (true, false) => "📌 ",
+        (true, true) => "* ",
+        (false, _) => "",
+    };
+    let parts = [
+        (Column::Index, opts.index(idx)),
+        (Column::Text, format!("{}{}", item.text, link_info(item, opts.ascii))),
+        (Column::Progress, progress_info(item)),
+        (Column::Time, time_info(item)),
+        (Column::Tags, tag_info(item)),
+        (Column::Priority, item.priority.map(|priority| format!("(priority: {})", priority)).unwrap_or_default()),
+        (Column::Due, due_info(item, now, opts.due_windows, opts.iso_dates).unwrap_or_default()),
+        (Column::Repeat, repeat_info),
+        (Column::Streak, streak_info(item, now, opts.ascii)),
+        (Column::Missed, missed_info(item)),
+        (Column::Start, start_info(item, now, opts.iso_dates).unwrap_or_default()),
+    ];
+    let lead = format!("{}{}{}", opts.dot(item.priority), marker, pin);
+    opts.task_output(idx, &lead, format!("{}{}", lead, opts.columns(&parts)))
+}
+
+/// A completed task's line (plus its completion note, if any).
+fn complete_line(idx: usize, item: &TodoItem, now: DateTime<Local>, opts: &ListOptions) -> String {
+    let limits = repeat_limit_info(item, opts.date_format());
+    let repeat_info = match item.time_until_next_repeat(now) {
+        Some(_) if opts.iso_dates => {
+            let next_due = item.next_due_start().unwrap_or(now);
+            format!("(repeat due {}{})", format_date(next_due, ISO_DATE_FORMAT), limits)
+        }
+        Some(_) if opts.repeat_display == RepeatDisplay::Days => {
+            let next_due = item.next_due_start().unwrap_or(now);
+            let days = (next_due.date_naive() - now.date_naive()).num_days();
+            let windows = opts.due_windows;
+            match days {
+                1.. if days <= windows.soon_days => format!("(due soon: {}d{})", days, limits),
+                1.. => format!("(repeat in {}d{})", days, limits),
+                0 => format!("(due today{})", limits),
+                _ if -days <= windows.grace_days => format!("(overdue {}d{})", -days, limits),
+                _ => format!("(OVERDUE {}d!{})", -days, limits),
+            }
+        }
+        Some(diff) => {
+            let hourly = matches!(item.repeat_spec, Some(RepeatSpec::Hours(_)));
+            if diff.num_seconds() <= 0 && hourly && diff.num_days() == 0 {
+                format!("(repeat: due now / overdue by {} hrs{})", -diff.num_hours(), limits)
+            } else if diff.num_seconds() <= 0 {
+                format!(
+                    "(repeat: due now / overdue by {} days{})",
+                    -diff.num_days(),
+                    limits
+                )
+            } else {
+                let days = diff.num_days();
+                let hours = (diff - Duration::days(days)).num_hours();
+                if days > 0 {
+                    format!("(repeat in {}d, {}hrs{})", days, hours, limits)
+                } else if hours > 0 {
+                    format!("(repeat in {} hrs{})", hours, limits)
+                } else {
+                    format!("(repeat in {} min{})", diff.num_minutes().max(1), limits)
+                }
+            }
+        }
+        None => {
+            if item.is_repeating() && item.repeat_finished() {
+                "(repeat finished)".to_string()
+            } else if item.is_repeating() {
+                "(repeat: no completion date yet)".to_string()
+            } else {
+                "(no repeat)".to_string()
+            }
+        }
+    };
+
+    let marker = if opts.mark_due && item.is_repeat_due_today(now) { "! " } else { "" };
+    let parts = [
+        (Column::Index, opts.index(idx)),
+        (Column::Text, format!("{}{}", item.text, link_info(item, opts.ascii))),
+        (Column::Progress, progress_info(item)),
+        (Column::Time, time_info(item)),
+        (Column::Tags, tag_info(item)),
+        (Column::Repeat, repeat_info),
+        (Column::Streak, streak_info(item, now, opts.ascii)),
+    ];
+    let lead = format!("{}{}", opts.dot(item.priority), marker);
+    let mut line = format!("{}{}", lead, opts.columns(&parts));
+    if let Some(note) = item.last_completion_note.as_ref().filter(|_| opts.shows(Column::Text)) {
+        line.push_str(&format!("\n    note: {}", note));
+    }
+    opts.task_output(idx, &lead, line)
+}
+
+/// A dot colored by priority (red, yellow, blue, grey for none); without
+/// color, "!", "+", "-" and "·" instead.
+fn priority_dot(priority: Option<Priority>, color: bool, ascii: bool) -> String {
+    let (paint, plain) = match priority {
+        Some(Priority::High) => (AnsiColor::Red, "!"),
+        Some(Priority::Medium) => (AnsiColor::Yellow, "+"),
+        Some(Priority::Low) => (AnsiColor::Blue, "-"),
+        None => (AnsiColor::Grey, if ascii { "." } else { "·" }),
+    };
+    match (color, ascii) {
+        (true, false) => paint.paint("●"),
+        (true, true) => paint.paint("o"),
+        (false, _) => plain.to_string(),
+    }
+}
+
+/// "Priority: ● high  ● medium  ● low  ● none", in the bullets' colors.
+fn priority_legend(color: bool, ascii: bool) -> String {
+    let levels = [Some(Priority::High), Some(Priority::Medium), Some(Priority::Low), None];
+    let entries: Vec<String> = levels
+    .iter()
+    .map(|level| {
+        let name = level.map_or("none".to_string(), |priority| priority.to_string());
+        format!("{} {}", priority_dot(*level, color, ascii), name)
+    })
+    .collect();
+    format!("Priority: {}", entries.join("  "))
+}
+
+fn task_line(idx: usize, item: &TodoItem, now: DateTime<Local>, opts: &ListOptions) -> String {
+    if item.complete {
+        complete_line(idx, item, now, opts)
+    } else {
+        incomplete_line(idx, item, now, opts)
+    }
+}
+
+/// Columns `text` takes up in a terminal: color codes take none and
+/// emoji two.
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                chars.by_ref().find(|c| *c == 'm');
+            }
+            '\u{1F000}'.. => width += 2,
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+/// Break each line of `text` between words so it fits in `width` columns,
+/// starting `start` columns in; continuation lines are indented by
+/// `indent`. A word too long for a line of its own is left whole.
+fn wrap_line(text: &str, width: usize, start: usize, indent: usize) -> String {
+    if indent >= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    for (n, line) in text.split('\n').enumerate() {
+        if n > 0 {
+            out.push('\n');
+        }
+        let mut column = if n == 0 { start } else { 0 };
+        for (i, word) in line.split(' ').enumerate() {
+            let word_width = display_width(word);
+            if i > 0 {
+                if column > indent && !word.is_empty() && column + 1 + word_width > width {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                } else {
+                    out.push(' ');
+                    column += 1;
+                }
+            }
+            out.push_str(word);
+            column += word_width;
+        }
+    }
+    out
+}
+
+/// The terminal's width, if stdout is one.
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+/// A section heading: "=== Title ===", or "-- Title --" in ASCII mode.
+pub fn heading(title: &str, ascii: bool) -> String {
+    if ascii {
+        format!("-- {} --", ascii_only(title))
+    } else {
+        format!("=== {} ===", title)
+    }
+}
+
+/// `text` with dashes turned into "-" and any other non-ASCII character
+/// into "?".
+pub fn ascii_only(text: &str) -> String {
+    text.chars()
+    .map(|c| match c {
+        c if c.is_ascii() => c,
+        '—' | '–' => '-',
+        _ => '?',
+    })
+    .collect()
+}
+
+/// How many repeating tasks are due and the first few of their names, e.g.
+/// "⚠ 2 repeating tasks are due: Feed gecko, Water plants"; `None` if
+/// nothing is due.
+fn due_banner(list: &TodoList, now: DateTime<Local>, ascii: bool) -> Option<String> {
+    const NAMES_SHOWN: usize = 5;
+
+    let due: Vec<&str> = list
+    .iter()
+    .filter(|item| item.is_repeat_due_today(now))
+    .map(|item| item.text.as_str())
+    .collect();
+    if due.is_empty() {
+        return None;
+    }
+
+    let mut names = due[..due.len().min(NAMES_SHOWN)].join(", ");
+    if due.len() > NAMES_SHOWN {
+        names.push_str(&format!(" and {} more", due.len() - NAMES_SHOWN));
+    }
+    Some(format!(
+        "{} {} repeating {} due: {}",
+        if ascii { "!" } else { "⚠" },
+        due.len(),
+        if due.len() == 1 { "task is" } else { "tasks are" },
+        names
+    ))
+}
+
+fn print_list(list: &TodoList, now: DateTime<Local>, opts: &ListOptions) {
+    if let Some(banner) = due_banner(list, now, opts.ascii).filter(|_| !opts.print0) {
+        println!("{}\n", opts.output(banner));
+    }
+
+    let mut sections = list.sections(now, opts.show_deferred, opts.insertion_order);
+    if let Some(key) = opts.sort {
+        let repeats_at = opts.sort_with_repeats.then_some(now);
+        list.sort_indexes(&mut sections.pinned, key, opts.reverse, repeats_at);
+        list.sort_indexes(&mut sections.incomplete, key, opts.reverse, repeats_at);
+    }
+    if let Some(since) = opts.new_since {
+        let is_new = |idx: &usize| list.items[*idx].created_at.is_some_and(|created| created > since);
+        sections.pinned.retain(is_new);
+        sections.incomplete.retain(is_new);
+        sections.complete.retain(is_new);
+    }
+    if let Some(last_day) = opts.due_before {
+        let is_due = |idx: &usize| {
+            let item = &list.items[*idx];
+            !item.complete && item.next_due(now).is_some_and(|due| due.date_naive() <= last_day)
+        };
+        sections.pinned.retain(is_due);
+        sections.incomplete.retain(is_due);
+        sections.complete.retain(is_due);
+    }
+    let mut in_inbox = 0;
+    if !opts.show_inbox {
+        let mut outside_inbox = |idx: &usize| {
+            let inbox = list.items[*idx].in_inbox();
+            in_inbox += usize::from(inbox);
+            !inbox
+        };
+        sections.pinned.retain(&mut outside_inbox);
+        sections.incomplete.retain(&mut outside_inbox);
+        sections.complete.retain(&mut outside_inbox);
+    }
+    let hidden = sections.hidden;
+
+    if opts.print0 {
+        for idx in [sections.pinned, sections.incomplete, sections.complete].concat() {
+            print!("{}\0", task_line(idx, &list.items[idx], now, opts));
+        }
+        return;
+    }
+
+    match opts.group_by {
+        _ if opts.flat => print_flat(list, sections, now, opts),
+        GroupBy::Status => print_status_sections(list, sections, now, opts),
+        GroupBy::Tag => print_tag_sections(list, sections, now, opts),
+    }
+
+    if hidden > 0 {
+        println!();
+        println!(
+            "{}",
+            opts.output(format!("({} deferred task(s) hidden — use --show-deferred to see them)", hidden))
+        );
+    }
+    if in_inbox > 0 {
+        println!();
+        println!(
+            "{}",
+            opts.output(format!("({} task(s) in the inbox — run `todo inbox` to triage them)", in_inbox))
+        );
+    }
+    if opts.priority_dots && opts.legend {
+        println!();
+        println!("{}", opts.output(priority_legend(opts.color, opts.ascii)));
+    }
+}
+
+fn print_status_sections(list: &TodoList, sections: ListSections, now: DateTime<Local>, opts: &ListOptions) {
+    if !sections.pinned.is_empty() {
+        println!("{}", opts.header("Pinned"));
+        for idx in sections.pinned {
+            println!("{}", incomplete_line(idx, &list.items[idx], now, opts));
+        }
+        println!();
+    }
+
+    println!("{}", opts.header("Incomplete tasks"));
+    if sections.incomplete.is_empty() {
+        println!("(none)");
+    } else {
+        for idx in sections.incomplete {
+            println!("{}", incomplete_line(idx, &list.items[idx], now, opts));
+        }
+    }
+
+    println!();
+    println!("{}", opts.header("Complete tasks"));
+    if sections.complete.is_empty() {
+        println!("(none)");
+    } else {
+        for idx in sections.complete {
+            println!("{}", complete_line(idx, &list.items[idx], now, opts));
+        }
+    }
+}
+
+/// The `diff` summary: tasks only on one side, then tasks whose state
+/// differs, each with what differs between "here" and "there".
+fn print_list_diff(list: &TodoList, other: &TodoList, other_path: &Path, date_format: &str) {
+    let diff = list.diff(other);
+    if diff.is_empty() {
+        println!("No differences from {}.", other_path.display());
+        return;
+    }
+
+    let section = |title: &str, items: &TodoList, indexes: &[usize]| {
+        if !indexes.is_empty() {
+            println!("{} ({}):", title, indexes.len());
+            for idx in indexes {
+                println!("  [{}] {}", idx, items.items[*idx].text);
+            }
+        }
+    };
+    section("Only here", list, &diff.only_here);
+    section(&format!("Only in {}", other_path.display()), other, &diff.only_there);
+
+    if !diff.changed.is_empty() {
+        println!("Changed ({}):", diff.changed.len());
+    }
+    let status = |item: &TodoItem| match item.complete_date.filter(|_| item.complete) {
+        Some(done) => format!("done {}", format_date(done, date_format)),
+        None if item.complete => "done".to_string(),
+        None => "open".to_string(),
+    };
+    let repeat = |item: &TodoItem| match repeat_label(item, WeekStart::default()) {
+        Some(label) => format!("repeat {}{}", label, repeat_limit_info(item, date_format)),
+        None => "no repeat".to_string(),
+    };
+    for (here, there) in diff.changed {
+        let (a, b) = (&list.items[here], &other.items[there]);
+        let mut changes = Vec::new();
+        for (mine, theirs) in [(status(a), status(b)), (repeat(a), repeat(b))] {
+            if mine != theirs {
+                changes.push(format!("{} here, {} there", mine, theirs));
+            }
+        }
+        let there = if here == there { String::new() } else { format!(" (there [{}])", there) };
+        println!("  [{}] {}{}: {}", here, a.text, there, changes.join("; "));
+    }
+}
+
+/// Every shown task in storage order, "[x]" or "[ ]" in front of each.
+fn print_flat(list: &TodoList, sections: ListSections, now: DateTime<Local>, opts: &ListOptions) {
+    let mut order = [sections.pinned, sections.incomplete, sections.complete].concat();
+    if order.is_empty() {
+        println!("(no tasks)");
+        return;
+    }
+    order.sort_unstable();
+    for idx in order {
+        let item = &list.items[idx];
+        let status = if item.complete { "[x]" } else { "[ ]" };
+        println!("{} {}", status, task_line(idx, item, now, opts));
+    }
+}
+
+/// One section per tag in alphabetical order (a task with several tags is
+/// listed under each), then the untagged tasks. Each section keeps the
+/// usual order: pinned, other incomplete, then complete.
+fn print_tag_sections(list: &TodoList, sections: ListSections, now: DateTime<Local>, opts: &ListOptions) {
+    let order = [sections.pinned, sections.incomplete, sections.complete].concat();
+    let mut tags: Vec<&str> = order
+    .iter()
+    .flat_map(|idx| list.items[*idx].tags.iter().map(String::as_str))
+    .collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    let mut first = true;
+    let mut section = |title: String, members: Vec<usize>| {
+        if members.is_empty() {
+            return;
+        }
+        if !first {
+            println!();
+        }
+        first = false;
+        println!("{}", opts.header(&title));
+        for idx in members {
+            println!("{}", task_line(idx, &list.items[idx], now, opts));
+        }
+    };
+
+    for tag in tags {
+        let members = order
+        .iter()
+        .copied()
+        .filter(|idx| list.items[*idx].tags.iter().any(|t| t == tag))
+        .collect();
+        section(format!("#{}", tag), members);
+    }
+    let untagged = order.iter().copied().filter(|idx| list.items[*idx].tags.is_empty()).collect();
+    section("(untagged)".to_string(), untagged);
+
+    if first {
+        println!("(no tasks)");
+    }
+}
+
+fn print_list_stats(stats: &ListStats, ascii: bool) {
+    println!("{}", heading("Stats", ascii));
+    println!("Total:              {}", stats.total);
+    println!("Complete:           {}", stats.complete);
+    println!("Incomplete:         {}", stats.incomplete);
+    println!("Repeating:          {}", stats.repeating);
+    println!("Due or overdue:     {}", stats.overdue);
+    match stats.oldest_incomplete_days {
+        Some(days) => println!("Oldest incomplete:  {} d", days),
+        None => println!("Oldest incomplete:  (unknown)"),
+    }
+    match stats.weighted_progress {
+        Some(percent) => println!("Weighted progress:  {:.1}%", percent),
+        None => println!("Weighted progress:  (no tasks)"),
+    }
+}
+
+/// A date as shown to people, in the configured `date_format`.
+pub fn format_date(date: DateTime<Local>, format: &str) -> String {
+    date.format(format).to_string()
+}
+
+/// One task with every field that's set, one per line.
+fn print_task_detail(index: usize, item: &TodoItem, date_format: &str, week_start: WeekStart) {
+    println!("[{}] {}{}{}", index, item.text, progress_info(item), time_info(item));
+    if !item.tags.is_empty() {
+        println!("  tags:     {}", tag_info(item).trim_start());
+    }
+    if let Some(priority) = item.priority {
+        println!("  priority: {}", priority);
+    }
+    if let Some(label) = repeat_label(item, week_start) {
+        println!("  repeat:   {}{}", label, repeat_limit_info(item, date_format));
+    }
+    if let Some(created) = item.created_at {
+        println!("  added:    {}", format_date(created, date_format));
+    }
+    if let Some(done) = item.complete_date.filter(|_| item.complete) {
+        println!("  done:     {}", format_date(done, date_format));
+    }
+    if let Some(notes) = &item.notes {
+        println!("  notes:    {}", notes);
+    }
+    if let Some(lead) = item.reminder_lead_minutes {
+        println!("  remind:   {} before the deadline", countdown(Duration::minutes(lead)));
+    }
+    if let Some(url) = &item.url {
+        println!("  link:     {}", url);
+    }
+    println!("  id:       #{}", item.id);
+}
+
+fn print_task_stats(index: usize, item: &TodoItem, now: DateTime<Local>, date_format: &str, ascii: bool) {
+    println!("{}", heading(&format!("Stats for [{}] {}", index, item.text), ascii));
+    println!("Completions recorded: {}", item.completion_history.len());
+    if let Some(last) = item.completion_history.last() {
+        println!("Last completed:       {}", format_date(*last, date_format));
+    }
+    if let Some(note) = item.last_completion_note.as_ref().filter(|_| item.complete) {
+        println!("Completion note:      {}", note);
+    }
+
+    match repeat_label(item, WeekStart::default()) {
+        Some(label) => {
+            println!("Repeat interval:      {}", label);
+            println!("Current streak:       {}", item.current_streak(now));
+            println!("Best streak:          {}", item.best_streak);
+        }
+        None => println!("Repeat interval:      (no repeat)"),
+    }
+
+    let Some((mean, median)) = item.completion_gap_stats() else {
+        println!("\nNeed at least two completions to measure intervals.");
+        return;
+    };
+
+    println!("Average gap:          {:.1} d", mean);
+    println!("Median gap:           {:.1} d", median);
+
+    if let (Some(days), None) = (item.repeat_days, &item.repeat_spec) {
+        let drift = mean - days as f64;
+        if drift.abs() < 0.05 {
+            println!("\nRight on schedule.");
+        } else if drift > 0.0 {
+            println!("\nOn average {:.1} d later than the repeat interval.", drift);
+        } else {
+            println!("\nOn average {:.1} d earlier than the repeat interval.", -drift);
+        }
+    }
+}
+
+/// "2 d" (or "4 h", "1 mo") for interval repeats, the weekdays in week
+/// order ("Sun, Wed"), or the preset's name ("weekly") while the schedule
+/// still matches it; followed by " at 08:00" when the task has a due time.
+fn repeat_label(item: &TodoItem, week_start: WeekStart) -> Option<String> {
+    let preset = item
+    .repeat_preset
+    .filter(|preset| preset.schedule() == (item.repeat_days, item.repeat_spec.clone()));
+    match &item.repeat_spec {
+        _ if preset.is_some() => preset.map(|preset| preset.name().to_string()),
+        Some(RepeatSpec::Weekdays(days)) => {
+            let mut days = days.clone();
+            days.sort_by_key(|day| day.days_since(week_start.weekday()));
+            let names: Vec<String> = days.iter().map(|day| day.to_string()).collect();
+            Some(names.join(", "))
+        }
+        Some(RepeatSpec::Hours(hours)) => Some(format!("{} h", hours)),
+        Some(RepeatSpec::Months(months)) => Some(format!("{} mo", months)),
+        None => item.repeat_days.map(|days| format!("{} d", days)),
+    }
+    .map(|label| match item.due_time.filter(|_| !matches!(item.repeat_spec, Some(RepeatSpec::Hours(_)))) {
+        Some(time) => format!("{} at {}", label, time.format("%H:%M")),
+        None => label,
+    })
+    .map(|label| match item.skip_weekends {
+        true => format!("{}, not on weekends", label),
+        false => label,
+    })
+}
+
+fn repeat_limit_info(item: &TodoItem, date_format: &str) -> String {
+    let mut info = String::new();
+    if let Some(count) = item.repeat_count {
+        info.push_str(&format!(", {} more", count));
+    }
+    if let Some(until) = item.repeat_until {
+        info.push_str(&format!(", until {}", format_date(until, date_format)));
+    }
+    info
+}
+
+fn tag_info(item: &TodoItem) -> String {
+    item.tags.iter().map(|t| format!(" #{}", t)).collect()
+}
+
+/// Time spent against the estimate, e.g. " (30/45 min)", flagged once
+/// the estimate is exceeded.
+fn time_info(item: &TodoItem) -> String {
+    match item.estimate_minutes {
+        Some(estimate) if item.spent_minutes > estimate => {
+            format!(" ({}/{} min) (over estimate)", item.spent_minutes, estimate)
+        }
+        Some(estimate) => format!(" ({}/{} min)", item.spent_minutes, estimate),
+        None if item.spent_minutes > 0 => format!(" ({} min spent)", item.spent_minutes),
+        None => String::new(),
+    }
+}
+
+/// " (🔥 5)" (" (streak 5)" in ASCII mode) while a repeating task has an
+/// active streak.
+fn streak_info(item: &TodoItem, now: DateTime<Local>, ascii: bool) -> String {
+    match item.current_streak(now) {
+        0 => String::new(),
+        streak if ascii => format!(" (streak {})", streak),
+        streak => format!(" (🔥 {})", streak),
+    }
+}
+
+/// " 🔗" (" (link)" in ASCII mode) when the task has a URL.
+fn link_info(item: &TodoItem, ascii: bool) -> String {
+    match (&item.url, ascii) {
+        (None, _) => String::new(),
+        (Some(_), true) => " (link)".to_string(),
+        (Some(_), false) => " 🔗".to_string(),
+    }
+}
+
+fn missed_info(item: &TodoItem) -> String {
+    match item.missed_count {
+        0 => String::new(),
+        missed => format!(" (missed {})", missed),
+    }
+}
+
+fn progress_info(item: &TodoItem) -> String {
+    match item.target {
+        Some(target) => format!(" ({}/{})", item.progress, target),
+        None => String::new(),
+    }
+}
+
+/// "(due in 3d)", "(due soon: 1d)", "(due today)", "(overdue 2d)" or,
+/// past the grace period, "(OVERDUE 5d!)" for a task with a deadline.
+/// With `iso`, the deadline itself replaces the day count.
+fn due_info(item: &TodoItem, now: DateTime<Local>, windows: DueWindows, iso: bool) -> Option<String> {
+    let status = windows.status(item, now)?;
+    if iso {
+        let due = format_date(item.due_date?, ISO_DATE_FORMAT);
+        return Some(match status {
+            DueStatus::Later(_) => format!("(due {})", due),
+            DueStatus::Soon(_) => format!("(due soon: {})", due),
+            DueStatus::Today => format!("(due today: {})", due),
+            DueStatus::Overdue(_) => format!("(overdue since {})", due),
+            DueStatus::LongOverdue(_) => format!("(OVERDUE since {}!)", due),
+        });
+    }
+    Some(match status {
+        DueStatus::Later(days) => format!("(due in {}d)", days),
+        DueStatus::Soon(days) => format!("(due soon: {}d)", days),
+        DueStatus::Today => "(due today)".to_string(),
+        DueStatus::Overdue(days) => format!("(overdue {}d)", days),
+        DueStatus::LongOverdue(days) => format!("(OVERDUE {}d!)", days),
+    })
+}
+
+fn start_info(item: &TodoItem, now: DateTime<Local>, iso: bool) -> Option<String> {
+    if !item.is_deferred(now) {
+        return None;
+    }
+
+    let start = item.start_date?;
+    if iso {
+        return Some(format!("(starts {})", format_date(start, ISO_DATE_FORMAT)));
+    }
+    let days = (start.date_naive() - now.date_naive()).num_days();
+    if days > 0 {
+        Some(format!("(starts in {}d)", days))
+    } else {
+        Some(format!("(starts in {} hrs)", (start - now).num_hours()))
+    }
+}
+
+/// Print the list as it stands now, returning what was shown.
+fn render_list(path: &PathBuf, opts: &ListOptions, passphrase: Option<&Passphrase>, catch_up: bool) -> Result<TodoList> {
+    let now = Local::now();
+    let mut list = TodoList::load(path, passphrase)?;
+    list.due_windows = opts.due_windows;
+    list.auto_reset_repeating(now, catch_up);
+    print_list(&list, now, opts);
+    Ok(list)
+}
+
+fn watch_list(path: &PathBuf, opts: &ListOptions, passphrase: Option<&Passphrase>, catch_up: bool) -> Result<()> {
+    // Piped or redirected output gets a single snapshot, no redraw loop.
+    if !std::io::stdout().is_terminal() {
+        render_list(path, opts, passphrase, catch_up)?;
+        return Ok(());
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_flag = Arc::clone(&running);
+    ctrlc::set_handler(move || handler_flag.store(false, Ordering::SeqCst))
+    .with_context(|| "Failed to install Ctrl-C handler")?;
+
+    // Watch the directory rather than the file itself, so editors and our own
+    // `save` replacing the file don't drop the watch.
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir)
+    .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+    .with_context(|| "Failed to start file watcher")?;
+    watcher
+    .watch(&dir, RecursiveMode::NonRecursive)
+    .with_context(|| format!("Failed to watch directory: {}", dir.display()))?;
+
+    let redraw = || -> Result<TodoList> {
+        print!("\x1B[2J\x1B[1;1H");
+        let shown = render_list(path, opts, passphrase, catch_up)?;
+        println!("\n{}", opts.output(format!("(watching {} — Ctrl-C to exit)", path.display())));
+        std::io::stdout().flush()?;
+        Ok(shown)
+    };
+
+    let mut shown = redraw()?;
+
+    while running.load(Ordering::SeqCst) {
+        let mut stale = false;
+        match rx.recv_timeout(StdDuration::from_millis(200)) {
+            Ok(Ok(event)) => {
+                // Our own reads show up as access events; only react to writes.
+                if matches!(event.kind, EventKind::Access(_)) {
+                    continue;
+                }
+
+                let touches_file = event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == path.file_name());
+                if touches_file {
+                    // `save` truncates before writing, so let the burst of
+                    // events settle before reading the file back.
+                    std::thread::sleep(StdDuration::from_millis(100));
+                    while rx.try_recv().is_ok() {}
+                    stale = true;
+                }
+            }
+            Ok(Err(err)) => eprintln!("Watch error: {}", err),
+            // Nothing was written, but a completed repeat may have come due.
+            Err(mpsc::RecvTimeoutError::Timeout) => stale = shown.auto_reset_repeating(Local::now(), catch_up) > 0,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if stale {
+            match redraw() {
+                Ok(list) => shown = list,
+                Err(err) => eprintln!("{:#}", err),
+            }
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Everything a bug report needs about this build and where it keeps data.
+fn print_version(path: &Path) {
+    println!("todoster {}", env!("CARGO_PKG_VERSION"));
+    println!("commit:  {}", env!("TODOSTER_GIT_COMMIT"));
+    println!("rustc:   {}", env!("TODOSTER_RUSTC_VERSION"));
+    println!("target:  {}", env!("TODOSTER_TARGET"));
+    println!("data:    {}", path.display());
+    println!("config:  {}", config_file_path().display());
+}
+
+fn command_row(usage: &str, description: &str) {
+    println!("{:<45} {}", usage, description);
+}
+
+fn print_command_table(ascii: bool) {
+    println!("{}\n", heading("Todoster Commands", ascii));
+
+    command_row("todo", "List tasks (default)");
+    command_row("todo list", "List tasks");
+
+    command_row("todo add \"<text>\"", "Add a new task");
+    command_row("todo add \"<text>\" --repeat <days>", "Add repeating task");
+
+    command_row("todo add \"<text>\" --repeat 4h", "Repeat every N hours");
+    command_row("todo add \"<text>\" --repeat weekly", "Also daily, fortnightly, monthly, weekday");
+    command_row("todo add \"<text>\" --repeat-on mon,thu", "Repeat on days of the week");
+    command_row("todo add \"<text>\" -r 1 --repeat-until <date>", "Stop repeating after a date");
+    command_row("todo add \"<text>\" -r 1 --repeat-count <n>", "Stop repeating after N more repeats");
+    command_row("todo add \"<text>\" --start <date>", "Hide the task until a date (alias --defer)");
+    command_row("todo add \"<text>\" --tag <tag>", "Add a tagged task (repeatable)");
+    command_row("todo list --show-deferred", "Include tasks that haven't started yet");
+    command_row("todo list --show-inbox", "Include tasks still in the inbox");
+    command_row("todo list --wrap <cols>", "Wrap task lines (0 = off; default: terminal)");
+    command_row("todo list --due-before <date>", "Agenda: only tasks due by that day");
+    command_row("todo list --no-legend", "Skip the priority legend under the list");
+    command_row("todo list --insertion-order", "Completed tasks in storage order (alias --reverse-complete)");
+    command_row("todo list --flat", "One storage-order list marked [x] / [ ]");
+    command_row("todo list --print0", "NUL-separated task lines only (xargs -0)");
+    command_row("todo list --group-by tag", "One section per tag, then untagged");
+    command_row("todo list --notify", "Desktop notification for due repeating tasks");
+
+    command_row("todo complete <index> [--note <text>]", "Mark a task complete, optionally with a note");
+    command_row("todo complete 0-3,7", "Complete several tasks (ranges like delete)");
+    command_row("todo complete last", "first/last: top or bottom task as listed");
+    command_row("todo complete -1", "Negative indexes count back from the bottom");
+    command_row("todo complete 0,2 --dry-run", "Preview when each would next be due");
+    command_row("todo complete --overdue", "Complete every due repeating task");
+    command_row("todo undo <index>", "Mark a task incomplete again");
+    command_row("todo undo 1-3", "Supports ranges (inclusive)");
+    command_row("todo toggle <indexes>", "Flip tasks between complete and incomplete");
+    command_row("todo skip <index>", "Skip one occurrence of a repeating task");
+    command_row("todo bump <index> <days>", "Add days to a repeat interval (negative shortens)");
+    command_row("todo pin <index> / todo unpin <index>", "Keep a task at the top of the list");
+    command_row("todo focus <index> / todo unfocus", "List only one task until you unfocus");
+    command_row("todo open <index>", "Open the task's link in your browser");
+    command_row("todo add <text> -r <days> --time <HH:MM>", "Repeat falls due at that time of day");
+    command_row("todo add <text> -r <days> --skip-weekends", "Weekend due dates move to Monday");
+    command_row("todo edit <index> --allow-weekends", "Undo --skip-weekends");
+    command_row("todo add <text> --priority <level>", "Add with a priority: low, medium or high");
+    command_row("todo add <text> --after <i> / --before <i>", "Insert next to a task instead of at the end");
+    command_row("todo add \"<text> !high #tag due:fri\"", "Quick add: priority, tags, deadline (--no-parse)");
+    command_row("todo add <text> --due <date>", "Give the task a deadline");
+    command_row("todo add <text> --target <n>", "Track progress towards a count, e.g. pages");
+    command_row("todo progress <index> [amount]", "Add progress (default 1); completes at target");
+    command_row("todo add <text> --estimate <min>", "Expected time in minutes");
+    command_row("todo add <text> --truncate", "Cut text over max_text_length instead of failing");
+    command_row("todo add --from-clipboard", "Add the copied text as a task (clipboard feature)");
+    command_row("todo track <index> --spent <min>", "Log time spent (shown as spent/estimate)");
+
+    command_row("todo edit <index> --text \"<new>\"", "Edit task text");
+    command_row("todo edit <index> --repeat <days>", "Change repeat interval");
+    command_row("todo edit <index> --clear-repeat", "Remove repeat interval");
+    command_row("todo edit <index> --start <date>", "Change start date");
+    command_row("todo edit <index> --clear-start", "Remove start date");
+    command_row("todo edit <index> --due <date>", "Change the deadline (--clear-due removes)");
+    command_row("todo edit <index> --notes \"<text>\"", "Set task notes (--clear-notes removes)");
+    command_row("todo edit <index> --priority <level>", "Set low/medium/high (--clear-priority removes)");
+    command_row("todo edit <index> --url <url>", "Set the task's link (--clear-url removes)");
+
+    command_row("todo delete <i1,i2,i3>", "Delete multiple tasks (by index)");
+    command_row("todo delete 1-4,7", "Supports ranges (inclusive)");
+    command_row("todo delete 1 3 5-6", "Spaces work too (also for complete)");
+    command_row("todo delete 0,2-3,7 --confirm", "Actually perform deletion");
+    command_row("todo delete 0,2-3,7", "Dry-run (shows what would be deleted)");
+    command_row("todo delete --completed --tag <tag>", "Delete by status and/or tag (also --incomplete)");
+    command_row("todo delete --all --confirm [--force]", "Delete every task (type DELETE unless --force)");
+
+    command_row("todo tag 0-3 <tag>... --remove <tag>", "Add/remove tags on several tasks");
+    command_row("todo add <text> --inbox", "Capture into the inbox, hidden from list");
+    command_row("todo inbox", "Show the tasks waiting to be triaged");
+    command_row("todo triage <index> [-p/-t/--due ...]", "Move out of the inbox, setting priority/tags/due");
+
+    command_row("todo random [--tag <tag>]", "Pick an incomplete task for you");
+    command_row("todo remind", "Due repeating tasks only; silent if none (cron)");
+    command_row("todo add \"<text>\" --remind-before 2h", "Have remind list it 2h before its deadline");
+    command_row("todo history", "Snapshots taken before each change");
+    command_row("todo version", "Version, commit, rustc, target and file paths");
+    command_row("todo doctor [--fix]", "Check (or repair) the data file; nonzero on problems");
+    command_row("todo edit-file", "Edit the data file in $EDITOR, then check it");
+    command_row("todo restore-snapshot <n>", "Roll back to snapshot n (1 = newest)");
+    command_row("todo replay-last", "Run the last command that changed the list again");
+    command_row("todo stats [--json]", "Task counts for the whole list");
+    command_row("todo stats-task <index>", "Completion history and average intervals");
+    command_row("todo merge <into> <from> --confirm", "Merge duplicates into one task");
+    command_row("todo dedupe [--ignore-case] [--confirm]", "Remove exact duplicates, keeping the richest copy");
+    command_row("todo replace <from> <to> [--confirm]", "Find and replace text across all tasks");
+
+    command_row("todo search <text> --limit <n>", "Find tasks by text (best matches first)");
+    command_row("todo search <text> --count", "Print just the number of matches");
+    command_row("todo search <text> --print0", "NUL-separated matches only (xargs -0)");
+    command_row("todo diff <other.ron>", "Compare with another list file (read-only)");
+
+    command_row("todo list --sort priority|created|due [--reverse]", "Order incomplete tasks");
+    command_row("todo list --sort due --with-repeats", "Also order repeats by next due");
+    command_row("todo list --new", "Only tasks added since the last `list`");
+    command_row("todo list --columns index,text,due", "Show only these fields, in this order");
+    command_row("todo list --json", "Print tasks as JSON");
+    command_row("todo export --format json|ron|markdown", "Export all tasks to stdout");
+    command_row("todo export --computed", "Add overdue_days/age_days to JSON");
+    command_row("todo export --to <file> [--force]", "Write to a file (format from extension)");
+    command_row("todo export --to-clipboard", "Copy instead of printing (clipboard feature)");
+    command_row("todo export --format todotxt", "todo.txt lines (`todo import todo.txt` reads them)");
+    command_row("todo import <file> [--format json|markdown]", "Merge tasks in (skips duplicate text)");
+    command_row("todo import <file> --replace", "Replace the whole list");
+    command_row("todo template-save <name> <text> [opts]", "Save a template (-r, -p, -t as for add)");
+    command_row("todo add-from-template <name>", "Add a task from a template");
+    command_row("todo templates", "List saved templates");
+
+    #[cfg(feature = "tui")]
+    command_row("todo tui", "Interactive UI (arrows, space, d, e, q)");
+    command_row("todo watch", "Re-render the list when the file changes");
+
+    command_row("todo --file <path> <command>", "Use a custom RON file");
+    command_row("todo --verbose <command>", "Print diagnostics to stderr");
+    command_row("todo --git <command>", "Commit the data file to git after changes");
+    command_row("todo --week-start sunday <command>", "First day of the week (default monday)");
+    command_row("todo --repeat-display days <command>", "Round repeat countdowns to whole days");
+    command_row("todo --yes <command>", "Skip the prompt for bulk complete/delete");
+    command_row("todo --no-catchup <command>", "Don't count repeats missed while away");
+    command_row("todo --iso-dates <command>", "RFC 3339 timestamps instead of countdowns");
+    command_row("todo --compact <command>", "Save the data file on one line, not pretty");
+    command_row("todo --quiet <command>", "Skip extras such as the exit summary");
+    command_row("todo --merge-files a.ron,b.ron", "Read-only combined list of several files");
+    command_row("todo --theme <name> <command>", "Use a configured color theme for this run");
+    command_row("todo --no-color <command>", "Disable colors/highlighting (or set NO_COLOR)");
+
+    println!("\nIndexes are currently 0-based (first item = 0).");
+
+    println!("\n{}\n", heading("Exit codes", ascii));
+    command_row("0", "Success");
+    command_row("1", "Other error");
+    command_row("2", "I/O error reading or writing the data file");
+    command_row("3", "Data file could not be parsed (or is from a newer version)");
+    command_row("4", "Invalid input (bad index, date or arguments)");
+}
+
+/// A single task as given on the command line: an index, `first` /
+/// `last` for the top and bottom of the list as `list` shows it, `-N`
+/// counting back from the bottom (`-1` is the last task), or `#<id>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskRef {
+    Index(usize),
+    First,
+    Last,
+    FromEnd(usize),
+    Id(u64),
+}
+
+impl std::str::FromStr for TaskRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "first" => Ok(TaskRef::First),
+            "last" => Ok(TaskRef::Last),
+            other => match (other.strip_prefix('-'), other.strip_prefix('#')) {
+                (Some(back), _) => back.parse().ok().filter(|n| *n > 0).map(TaskRef::FromEnd),
+                (_, Some(id)) => id.parse().ok().map(TaskRef::Id),
+                _ => other.parse().ok().map(TaskRef::Index),
+            }
+            .ok_or_else(|| format!("expected an index, \"first\", \"last\", \"-N\" or \"#id\", got \"{}\"", s)),
+        }
+    }
+}
+
+impl TaskRef {
+    /// The storage index this refers to. Keywords follow the current
+    /// display order (see `TodoList::display_order`), so resolve after
+    /// auto-reset.
+    pub fn resolve(self, list: &TodoList, now: DateTime<Local>, sort: Option<(SortKey, bool)>) -> Result<usize> {
+        let order = list.display_order(now, sort);
+        let found = match self {
+            TaskRef::Index(idx) => return Ok(idx),
+            TaskRef::Id(id) => {
+                return list.find_by_id(id).ok_or_else(|| invalid_input(format!("No task with id #{}", id)));
+            }
+            TaskRef::First => order.first(),
+            TaskRef::Last => order.last(),
+            TaskRef::FromEnd(back) => {
+                return order.len().checked_sub(back).map(|pos| order[pos]).ok_or_else(|| {
+                    invalid_input(format!(
+                        "Index -{} is out of range; the list has {} task(s).",
+                        back,
+                        order.len()
+                    ))
+                });
+            }
+        };
+        found
+        .copied()
+        .ok_or_else(|| invalid_input("The list is empty; there is no first or last task."))
+    }
+}
+
+/// Guard against fat-fingered ranges: past `threshold` tasks, ask on a
+/// terminal and otherwise insist on `--yes`.
+fn confirm_bulk(count: usize, threshold: usize, yes: bool) -> Result<()> {
+    if count <= threshold || yes {
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(invalid_input(format!(
+            "About to affect {} tasks (more than {}); pass --yes to proceed.",
+            count, threshold
+        )));
+    }
+
+    print!("About to affect {} tasks. Continue? [y/N] ", count);
+    std::io::stdout().flush().map_err(TodoError::Io)?;
+    let mut answer = String::new();
+    std::io::stdin()
+    .read_line(&mut answer)
+    .map_err(TodoError::Io)
+    .with_context(|| "Failed to read confirmation")?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => Err(invalid_input("Aborted; nothing changed.")),
+    }
+}
+
+// Make this public so tests (and main.rs) can use it.
+pub fn parse_index_list(spec: &str) -> Vec<usize> {
+    split_index_list(spec).0
+}
+
+/// Like `parse_index_list`, but returns the parts that aren't an index or
+/// a range (e.g. `abc`, `2-`, `3x`) instead of silently dropping them.
+pub fn parse_index_list_checked(spec: &str) -> std::result::Result<Vec<usize>, Vec<String>> {
+    match split_index_list(spec) {
+        (indices, invalid) if invalid.is_empty() => Ok(indices),
+        (_, invalid) => Err(invalid),
+    }
+}
+
+/// Parse an index list for a command that also takes `first`, `last` and
+/// `-N` parts, resolved against the current display order. Other invalid
+/// parts are warned about and ignored, as with plain index lists.
+fn cli_task_list(spec: &str, list: &TodoList, now: DateTime<Local>, sort: Option<(SortKey, bool)>) -> Result<Vec<usize>> {
+    let mut indices = Vec::new();
+    let mut rest = Vec::new();
+    for part in spec.split(',') {
+        match part.parse::<TaskRef>() {
+            Ok(TaskRef::Index(_)) | Err(_) => rest.push(part),
+            Ok(task) => indices.push(task.resolve(list, now, sort)?),
+        }
+    }
+    indices.extend(cli_index_list(&rest.join(",")));
+    Ok(indices)
+}
+
+/// Parse an index list for a command, warning about any ignored parts.
+fn cli_index_list(spec: &str) -> Vec<usize> {
+    parse_index_list_checked(spec).unwrap_or_else(|invalid| {
+        eprintln!("Ignoring invalid index part(s): {}", invalid.join(", "));
+        parse_index_list(spec)
+    })
+}
+
+/// The valid indexes in `spec`, plus every non-empty part that wasn't one.
+fn split_index_list(spec: &str) -> (Vec<usize>, Vec<String>) {
+    let mut result = Vec::new();
+    let mut invalid = Vec::new();
+
+    for part in spec.split(',') {
+        let p = part.trim();
+        if p.is_empty() {
+            continue;
+        }
+
+        if let Some((start_s, end_s)) = p.split_once('-') {
+            let (Ok(start), Ok(end)) = (start_s.trim().parse::<usize>(), end_s.trim().parse::<usize>()) else {
+                invalid.push(p.to_string());
+                continue;
+            };
+
+            if start <= end {
+                for i in start..=end {
+                    result.push(i);
+                }
+            } else {
+                for i in end..=start {
+                    result.push(i);
+                }
+            }
+        } else if let Ok(v) = p.parse::<usize>() {
+            result.push(v);
+        } else {
+            invalid.push(p.to_string());
+        }
+    }
+
+    (result, invalid)
+}
+
+/// Levenshtein distance between two words, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != *cb)).min(above + 1).min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The command name `typed` most likely meant: one it is the start of
+/// (`comp` → `complete`), else the closest within two edits.
+pub fn suggest_command<'a>(typed: &str, names: &[&'a str]) -> Option<&'a str> {
+    let typed = typed.to_lowercase();
+    let closest = |candidates: &mut dyn Iterator<Item = &'a str>| candidates.min_by_key(|name| (edit_distance(&typed, name), *name));
+    closest(&mut names.iter().copied().filter(|name| name.starts_with(&typed)))
+    .or_else(|| closest(&mut names.iter().copied().filter(|name| edit_distance(&typed, name) <= 2)))
+}
+
+/// Replace clap's list of similar subcommands with our single best guess.
+fn suggest_for_unknown_command(mut err: clap::Error) -> clap::Error {
+    if err.kind() != ErrorKind::InvalidSubcommand {
+        return err;
+    }
+    let Some(ContextValue::String(typed)) = err.get(ContextKind::InvalidSubcommand).cloned() else {
+        return err;
+    };
+    let command = Cli::command();
+    let names: Vec<&str> = command
+    .get_subcommands()
+    .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_visible_aliases()))
+    .collect();
+    if let Some(name) = suggest_command(&typed, &names) {
+        err.insert(ContextKind::SuggestedSubcommand, ContextValue::Strings(vec![name.to_string()]));
+    }
+    err
+}
+
+/// Re-run the command `ViewState::remember_command` stored for `path`.
+fn replay_last(path: &Path, now: DateTime<Local>) -> Result<()> {
+    let Some(args) = ViewState::load(&ViewState::path_for(path)).last_command else {
+        return Err(invalid_input("Nothing to replay yet: no command has changed this list."));
+    };
+    let shown = args
+    .iter()
+    .map(|arg| if arg.contains(char::is_whitespace) { format!("{:?}", arg) } else { arg.clone() })
+    .collect::<Vec<_>>()
+    .join(" ");
+    let cli = Cli::try_parse_from(std::iter::once("todo".to_string()).chain(args.iter().cloned()))
+    .map_err(TodoError::Usage)?;
+    // Only ever stored for commands that change the list, but the file is
+    // plain text and could have been edited.
+    if cli.command.as_ref().is_none_or(|command| command.git_message().is_none()) {
+        return Err(invalid_input(format!(
+            "`todo {}` doesn't change the list, so it isn't replayed.",
+            shown
+        )));
+    }
+    println!("Replaying: todo {}", shown);
+    run_with(cli, now)
+}
+
+/// Public entry point that main.rs will call.
+pub fn run_cli() -> Result<()> {
+    let started = Instant::now();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // --help and --version are not failures; let clap print and exit 0.
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => return Err(TodoError::Usage(suggest_for_unknown_command(err)).into()),
+    };
+
+    let verbose = cli.verbose;
+    let replayable = cli.command.as_ref().is_some_and(|command| command.git_message().is_some());
+    let path = cli.file.clone().unwrap_or_else(default_file_path);
+    let result = run_with(cli, Local::now());
+    if result.is_ok() && replayable {
+        let args = env::args_os().skip(1).map(|arg| arg.to_string_lossy().into_owned()).collect();
+        ViewState::remember_command(&path, args);
+    }
+    if verbose {
+        eprintln!("[verbose] finished in {:.2?}", started.elapsed());
+    }
     result
 }
 
-/// Public entry point that main.rs will call.
-pub fn run_cli() -> Result<()> {
-    let cli = Cli::parse();
-    let path = cli.file.clone().unwrap_or_else(default_file_path);
+/// Run a parsed command as if the time were `now`, which every due date,
+/// repeat reset and timestamp it records is measured against (`watch` still
+/// follows the clock). `run_cli` passes `Local::now()`; tests can pin it.
+pub fn run_with(cli: Cli, now: DateTime<Local>) -> Result<()> {
+    let path = cli.file.clone().unwrap_or_else(default_file_path);
+    if matches!(cli.command, Some(Commands::Version)) {
+        print_version(&path);
+        return Ok(());
+    }
+    if matches!(cli.command, Some(Commands::ReplayLast)) {
+        return replay_last(&path, now);
+    }
+    let passphrase = resolve_passphrase(cli.encrypt, &path)?;
+    let passphrase = passphrase.as_ref();
+    // Before the legacy move and `load`, both of which may write.
+    if let Some(Commands::Doctor { fix }) = cli.command {
+        if doctor(&path, fix, passphrase, now)? && cli.git {
+            git_commit(&path, "todoster: repair data file")?;
+        }
+        return Ok(());
+    }
+    if cli.file.is_none() && migrate_data_file(&legacy_file_path(), &path)? {
+        eprintln!(
+            "Moved your tasks from {} to {} (data now lives under XDG_DATA_HOME).",
+            legacy_file_path().display(),
+            path.display()
+        );
+    }
+    // Also before `load`: a file that no longer parses is what it's for.
+    if matches!(cli.command, Some(Commands::EditFile)) {
+        if edit_file(&path)? && cli.git {
+            git_commit(&path, "todoster: edit data file")?;
+        }
+        return Ok(());
+    }
+    let config = Config::load(&config_file_path())?;
+    let week_start = cli.week_start.unwrap_or(config.week_start);
+    let repeat_display = cli.repeat_display.unwrap_or(config.repeat_display);
+    let bulk_threshold = config.bulk_threshold.unwrap_or(DEFAULT_BULK_THRESHOLD);
+    let date_format = match cli.iso_dates {
+        true => ISO_DATE_FORMAT,
+        false => config.date_format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT),
+    };
+    let due_windows = config.due_windows();
+    let max_text_length = config.max_text_length;
+    let default_sort = config.sort_key().map(|key| (key, config.sort_reverse));
+    let theme = config.theme_for(list_name(&path), cli.theme.as_deref())?;
+    let theme = Some(theme).filter(|_| color_enabled(cli.no_color));
+
+    let merging = !cli.merge_files.is_empty();
+    if merging && !matches!(cli.command, None | Some(Commands::List { .. })) {
+        return Err(invalid_input(
+            "--merge-files is a read-only view; only `list` can be used with it.",
+        ));
+    }
+    let mut list = match merging {
+        true => load_merged(&cli.merge_files, passphrase)?,
+        false => TodoList::load(&path, passphrase)?,
+    };
+    list.due_windows = due_windows;
+    list.compact = cli.compact || config.compact;
+
+    if cli.verbose {
+        if merging {
+            for file in &cli.merge_files {
+                eprintln!("[verbose] merged file: {}", file.display());
+            }
+        } else {
+            let state = if path.exists() { "loaded" } else { "not found, starting empty" };
+            eprintln!("[verbose] storage file: {} ({})", path.display(), state);
+        }
+        eprintln!("[verbose] parsed {} item(s)", list.items.len());
+    }
+
+    // Auto-reset repeating items that are due
+    let reset = list.auto_reset_repeating(now, !cli.no_catchup);
+    if cli.verbose {
+        eprintln!("[verbose] auto-reset {} repeating task(s)", reset);
+    }
+
+    let default_command = Commands::List {
+        show_deferred: false,
+        notify: false,
+        json: false,
+        insertion_order: false,
+        group_by: GroupBy::Status,
+        sort: None,
+        reverse: false,
+        with_repeats: false,
+        new: false,
+        columns: Vec::new(),
+        flat: false,
+        show_inbox: false,
+        no_legend: false,
+        due_before: None,
+        wrap: None,
+        print0: false,
+    };
+    let command = cli.command.unwrap_or(default_command);
+    let mutation = command.git_message();
+    let git_message = mutation.filter(|_| cli.git);
+    let before = mutation.map(|_| list.clone());
+
+    match command {
+        Commands::List {
+            show_deferred,
+            notify,
+            json,
+            insertion_order,
+            group_by,
+            sort,
+            reverse,
+            with_repeats,
+            new,
+            columns,
+            flat,
+            show_inbox,
+            no_legend,
+            due_before,
+            wrap,
+            print0,
+        } => {
+            let due_before = match due_before {
+                Some(spec) => Some(parse_date_spec(&spec, now)?.date_naive()),
+                None => None,
+            };
+            // A merged view isn't any one list, so it doesn't count as a look.
+            let last_viewed = match merging {
+                true => None,
+                false => ViewState::record(&ViewState::path_for(&path), now),
+            };
+            let new_since = match (new, last_viewed) {
+                (true, Some(since)) => {
+                    println!("(tasks added since {})\n", format_date(since, date_format));
+                    Some(since)
+                }
+                (true, None) => {
+                    println!("(first look: everything is new)\n");
+                    None
+                }
+                (false, _) => None,
+            };
+
+            // An explicit --sort (with or without --reverse) replaces the config's.
+            let (sort, reverse) = match (sort, default_sort) {
+                (None, Some((key, reverse))) => (Some(key), reverse),
+                _ => (sort, reverse),
+            };
+            let mark_due = notify && !notify_due(&list, now);
+            if json {
+                println!("{}", list.to_json(now, false)?);
+                return Ok(());
+            }
+            // Scripts get every task, focused or not.
+            if let Some(idx) = list.focused().filter(|_| !print0) {
+                println!("{}", heading("Focus", cli.ascii));
+                print_task_detail(idx, &list.items[idx], date_format, week_start);
+                println!("\n(run `todo unfocus` to see every task)");
+                return Ok(());
+            }
+            // Picked columns are exactly what's shown; otherwise bullets
+            // only once something has a priority to tell apart.
+            let priority_dots = columns.is_empty() && !print0 && list.items.iter().any(|item| item.priority.is_some());
+            print_list(
+                &list,
+                now,
+                &ListOptions {
+                    show_deferred,
+                    mark_due,
+                    week_start,
+                    insertion_order,
+                    theme: theme.filter(|_| !print0),
+                    repeat_display,
+                    group_by,
+                    sort,
+                    reverse,
+                    sort_with_repeats: with_repeats,
+                    new_since,
+                    due_before,
+                    ascii: cli.ascii,
+                    due_windows,
+                    columns,
+                    iso_dates: cli.iso_dates,
+                    date_format: Some(date_format.to_string()),
+                    flat,
+                    show_inbox,
+                    priority_dots,
+                    legend: !no_legend,
+                    color: color_enabled(cli.no_color),
+                    wrap: wrap.or(config.wrap).or_else(terminal_width).filter(|width| *width > 0 && !print0),
+                    print0,
+                },
+            );
+        }
+
+        Commands::Add {
+            text,
+            from_clipboard,
+            inbox,
+            repeat,
+            repeat_on,
+            repeat_until,
+            repeat_count,
+            time,
+            skip_weekends,
+            start,
+            due,
+            remind_before,
+            no_parse,
+            target,
+            estimate,
+            priority,
+            after,
+            before,
+            tags,
+            notes,
+            url,
+            truncate,
+        } => {
+            let position = match (after, before) {
+                (Some(task), _) => Some((task.resolve(&list, now, default_sort)?, 1)),
+                (None, Some(task)) => Some((task.resolve(&list, now, default_sort)?, 0)),
+                (None, None) => None,
+            };
+            if let Some((idx, _)) = position.filter(|(idx, _)| *idx >= list.items.len()) {
+                return Err(invalid_input(format!("No task with index {}", idx)));
+            }
+
+            let text = match from_clipboard {
+                true => {
+                    let copied = clipboard_text()?.trim().to_string();
+                    if copied.is_empty() {
+                        return Err(invalid_input("The clipboard has no text to add."));
+                    }
+                    copied
+                }
+                false => text.unwrap_or_default(),
+            };
+            let quick = match no_parse {
+                true => QuickAdd { text, ..QuickAdd::default() },
+                false => parse_quick_add(&text, now)?,
+            };
+            if quick.text.is_empty() && !no_parse {
+                return Err(invalid_input(
+                    "Task text is empty once quick-add tokens are taken out (use --no-parse to keep them).",
+                ));
+            }
+            let (text, truncated) = validate_task_text(quick.text, max_text_length, truncate)?;
+            if truncated {
+                eprintln!("Task text cut to {} characters.", max_text_length.unwrap_or_default());
+            }
+
+            let repeat = repeat.map(RepeatInterval::validate).transpose()?;
+            if target == Some(0) {
+                return Err(invalid_input("Target must be at least 1."));
+            }
+            if estimate == Some(0) {
+                return Err(invalid_input("Estimate must be at least 1 minute."));
+            }
+            let url = url.as_deref().map(validate_url).transpose()?;
+            let mut item = TodoItem::new(text, None);
+            if let Some(interval) = repeat {
+                item.set_repeat_interval(interval);
+            }
+            item.add_tags(&quick.tags);
+            item.add_tags(&tags);
+            if inbox {
+                item.add_tags(&[INBOX_TAG.to_string()]);
+            }
+            item.notes = notes;
+            item.url = url;
+            item.created_at = Some(now);
+            item.repeat_count = repeat_count;
+            item.target = target;
+            item.estimate_minutes = estimate;
+            item.priority = priority.or(quick.priority);
+            item.due_date = match due {
+                Some(spec) => Some(parse_date_spec(&spec, now)?),
+                None => quick.due,
+            };
+            item.due_time = time.as_deref().map(parse_time_of_day).transpose()?;
+            item.reminder_lead_minutes = remind_before.as_deref().map(parse_lead_time).transpose()?;
+            item.skip_weekends = skip_weekends;
+            if let Some(spec) = repeat_on {
+                item.repeat_spec = Some(RepeatSpec::Weekdays(parse_weekdays(&spec)?));
+            }
+            if let Some(spec) = repeat_until {
+                item.repeat_until = Some(parse_date_spec(&spec, now)?);
+            }
+            if let Some(spec) = start {
+                item.start_date = Some(parse_date_spec(&spec, now)?);
+            }
+            match position {
+                Some((idx, offset)) => {
+                    let at = list.insert_at(idx + offset, item);
+                    list.save(&path)?;
+                    println!("Task added at [{}].", at);
+                }
+                None => {
+                    list.add(item);
+                    list.save(&path)?;
+                    println!("Task added.");
+                }
+            }
+        }
+
+        Commands::TemplateSave {
+            name,
+            text,
+            repeat,
+            priority,
+            tags,
+        } => {
+            let repeat = repeat.map(RepeatInterval::validate).transpose()?;
+            let (repeat_days, repeat_spec, repeat_preset) = repeat.map(RepeatInterval::schedule).unwrap_or_default();
+            let path = templates_file_path();
+            let mut store = TemplateStore::load(&path)?;
+            let template = Template {
+                text,
+                repeat_days,
+                repeat_spec,
+                repeat_preset,
+                tags,
+                priority,
+            };
+            let replaced = store.templates.insert(name.clone(), template).is_some();
+            store.save(&path)?;
+            println!("Template \"{}\" {}.", name, if replaced { "updated" } else { "saved" });
+        }
+
+        Commands::AddFromTemplate { name } => {
+            let store = TemplateStore::load(&templates_file_path())?;
+            let item = store.get(&name)?.instantiate(now);
+            list.add(item);
+            list.save(&path)?;
+            println!("Task added from template \"{}\" at [{}].", name, list.items.len() - 1);
+        }
+
+        Commands::Templates => {
+            let store = TemplateStore::load(&templates_file_path())?;
+            if store.templates.is_empty() {
+                println!("No templates yet; save one with `template-save <name> <text>`.");
+                return Ok(());
+            }
+            for (name, template) in &store.templates {
+                let item = template.instantiate(now);
+                let repeat = repeat_label(&item, week_start).map(|label| format!(" (Repeat: {})", label));
+                let priority = template.priority.map(|p| format!(" (priority: {})", p));
+                println!(
+                    "{:<16} {}{}{}{}",
+                    name,
+                    template.text,
+                    tag_info(&item),
+                    priority.unwrap_or_default(),
+                    repeat.unwrap_or_default()
+                );
+            }
+        }
+
+        Commands::Complete {
+            indexes,
+            overdue,
+            note,
+            dry_run,
+        } => {
+            let mut indices = match overdue {
+                true => list
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| !item.complete && item.is_repeat_due_today(now))
+                .map(|(idx, _)| idx)
+                .collect(),
+                false => cli_task_list(&indexes.join(","), &list, now, default_sort)?,
+            };
+            if overdue && indices.is_empty() {
+                println!("No repeating tasks are due.");
+                return Ok(());
+            }
+            indices.sort_unstable();
+            indices.dedup();
+            if indices.is_empty() {
+                return Err(invalid_input("No valid indexes supplied."));
+            }
+            if let Some(missing) = indices.iter().find(|idx| **idx >= list.items.len()) {
+                return Err(invalid_input(format!("No task with index {}", missing)));
+            }
+            if dry_run {
+                for idx in &indices {
+                    let mut preview = list.items[*idx].clone();
+                    preview.mark_complete(now);
+                    match preview.next_due_start() {
+                        Some(next_due) if !preview.repeat_finished() => {
+                            println!("Task {}: next due {}", idx, format_date(next_due, date_format))
+                        }
+                        Some(_) => println!("Task {}: (repeat finished)", idx),
+                        None => println!("Task {}: (no repeat)", idx),
+                    }
+                }
+                println!("Dry run: nothing completed.");
+                return Ok(());
+            }
+            confirm_bulk(indices.len(), bulk_threshold, cli.yes)?;
+
+            let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+            for idx in &indices {
+                let item = &mut list.items[*idx];
+                item.mark_complete(now);
+                item.last_completion_note = note.clone();
+            }
+            list.save(&path)?;
+            for idx in &indices {
+                let item = &list.items[*idx];
+                match item.next_due_start().filter(|_| overdue && !item.repeat_finished()) {
+                    Some(next_due) => {
+                        println!("Task {} marked complete; next due {}.", idx, format_date(next_due, date_format))
+                    }
+                    None => println!("Task {} marked complete.", idx),
+                }
+            }
+        }
+
+        Commands::Progress { index, amount } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get_mut(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+
+            let Some(target) = item.target else {
+                return Err(invalid_input(format!(
+                    "Task {} has no target; add one with `add --target <n>`.",
+                    index
+                )));
+            };
+            if item.complete {
+                return Err(invalid_input(format!("Task {} is already complete.", index)));
+            }
+
+            if item.add_progress(amount, now) {
+                println!("Task {} reached its target ({}/{}) and is complete.", index, target, target);
+            } else {
+                println!("Task {} progress: {}/{}.", index, item.progress, target);
+            }
+            list.save(&path)?;
+        }
+
+        Commands::Track { index, spent } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get_mut(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+            if spent == 0 {
+                return Err(invalid_input("Nothing to track; --spent must be at least 1 minute."));
+            }
+
+            item.spent_minutes = item.spent_minutes.saturating_add(spent);
+            println!("Logged {} min on task {}{}.", spent, index, time_info(item));
+            list.save(&path)?;
+        }
+
+        Commands::Pin { index } | Commands::Unpin { index } => {
+            let pin = matches!(command, Commands::Pin { .. });
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get_mut(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+
+            item.pinned = pin;
+            list.save(&path)?;
+            println!("Task {} {}.", index, if pin { "pinned" } else { "unpinned" });
+        }
+
+        Commands::Focus { index } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            if index >= list.items.len() {
+                return Err(invalid_input(format!("No task with index {}", index)));
+            }
+
+            for (idx, item) in list.items.iter_mut().enumerate() {
+                item.focused = idx == index;
+            }
+            list.save(&path)?;
+            println!("Focusing on task {}. Run `todo unfocus` to see every task.", index);
+        }
+
+        Commands::Open { index } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let Some(item) = list.items.get(index) else {
+                return Err(invalid_input(format!("No task with index {}", index)));
+            };
+            let Some(url) = &item.url else {
+                return Err(invalid_input(format!(
+                    "Task {} has no link (add one with `todo edit {} --url <url>`).",
+                    index, index
+                )));
+            };
+            open_url(url)?;
+            println!("Opened {}", url);
+        }
 
-    let now = Local::now();
-    let mut list = TodoList::load(&path)?;
+        Commands::Unfocus => match list.focused() {
+            Some(_) => {
+                for item in &mut list.items {
+                    item.focused = false;
+                }
+                list.save(&path)?;
+                println!("Focus cleared.");
+            }
+            None => println!("No task is focused."),
+        },
 
-    // Auto-reset repeating items that are due
-    list.auto_reset_repeating(now);
+        Commands::Toggle { indexes } => {
+            let mut indices = cli_task_list(&indexes, &list, now, default_sort)?;
+            indices.sort_unstable();
+            indices.dedup();
+            if indices.is_empty() {
+                return Err(invalid_input("No valid indexes supplied."));
+            }
+            confirm_bulk(indices.len(), bulk_threshold, cli.yes)?;
+
+            let mut changed = 0;
+            for idx in indices {
+                let Some(item) = list.items.get_mut(idx) else {
+                    eprintln!("Index {} does not exist - skipping.", idx);
+                    continue;
+                };
+                let state = if item.toggle(now) { "complete" } else { "incomplete" };
+                println!("Task {} is now {}.", idx, state);
+                changed += 1;
+            }
 
-    match cli.command.unwrap_or(Commands::List) {
-        Commands::List => {
-            print_list(&list, now);
+            if changed > 0 {
+                list.save(&path)?;
+            }
         }
 
-        Commands::Add { text, repeat } => {
-            list.add(text, repeat);
+        Commands::Bump { index, delta } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get_mut(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+
+            let Some(old) = item.repeat_days else {
+                return Err(invalid_input(match item.repeat_spec {
+                    Some(RepeatSpec::Weekdays(_)) => {
+                        format!("Task {} repeats on weekdays; only day intervals can be bumped.", index)
+                    }
+                    Some(RepeatSpec::Hours(_)) => {
+                        format!("Task {} repeats every few hours; only day intervals can be bumped.", index)
+                    }
+                    Some(RepeatSpec::Months(_)) => {
+                        format!("Task {} repeats monthly; only day intervals can be bumped.", index)
+                    }
+                    None => format!("Task {} doesn't repeat; set one with `edit --repeat` first.", index),
+                }));
+            };
+            let new = old.saturating_add(delta).max(1);
+            item.repeat_days = Some(new);
             list.save(&path)?;
-            println!("Task added.");
+            println!("Task {} now repeats every {} day(s) (was {}).", index, new, old);
         }
 
-        Commands::Complete { index } => {
-            if let Some(item) = list.items.get_mut(index) {
-                item.mark_complete(now);
-                list.save(&path)?;
-                println!("Task {} marked complete.", index);
-            } else {
-                eprintln!("No task with index {}", index);
+        Commands::Skip { index } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get_mut(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+
+            if item.complete && item.repeat_finished() {
+                return Err(invalid_input(format!("Task {} has finished repeating.", index)));
+            }
+            if !item.skip_occurrence(now) {
+                return Err(invalid_input(format!(
+                    "Task {} doesn't repeat; only repeating tasks can be skipped.",
+                    index
+                )));
+            }
+
+            match item.next_due_start() {
+                Some(next_due) => println!(
+                    "Skipped task {}; next due {}.",
+                    index,
+                    format_date(next_due, date_format)
+                ),
+                None => println!("Skipped task {}.", index),
             }
+            list.save(&path)?;
         }
 
-        Commands::Undo { index } => {
-            if let Some(item) = list.items.get_mut(index) {
+        Commands::Undo { indexes } => {
+            let mut indices = cli_task_list(&indexes, &list, now, default_sort)?;
+            indices.sort_unstable();
+            indices.dedup();
+            if indices.is_empty() {
+                return Err(invalid_input("No valid indexes supplied."));
+            }
+
+            let mut changed = 0;
+            for idx in indices {
+                let Some(item) = list.items.get_mut(idx) else {
+                    eprintln!("Index {} does not exist - skipping.", idx);
+                    continue;
+                };
+                if !item.complete {
+                    println!("Task {} is already incomplete.", idx);
+                    continue;
+                }
                 item.mark_incomplete();
+                println!("Task {} marked incomplete.", idx);
+                changed += 1;
+            }
+
+            if changed > 0 {
                 list.save(&path)?;
-                println!("Task {} marked incomplete.", index);
-            } else {
-                eprintln!("No task with index {}", index);
             }
         }
 
         Commands::Edit {
             index,
             text,
+            truncate,
             repeat,
+            repeat_on,
             clear_repeat,
+            repeat_until,
+            repeat_count,
+            time,
+            clear_time,
+            skip_weekends,
+            allow_weekends,
+            start,
+            clear_start,
+            due,
+            clear_due,
+            remind_before,
+            clear_remind,
+            priority,
+            clear_priority,
+            notes,
+            clear_notes,
+            url,
+            clear_url,
         } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let url = url.as_deref().map(validate_url).transpose()?;
+            let due_time = time.as_deref().map(parse_time_of_day).transpose()?;
+            let reminder_lead = remind_before.as_deref().map(parse_lead_time).transpose()?;
+            let start_date = match start {
+                Some(spec) => Some(parse_date_spec(&spec, now)?),
+                None => None,
+            };
+            let due_date = match due {
+                Some(spec) => Some(parse_date_spec(&spec, now)?),
+                None => None,
+            };
+            let repeat_until = match repeat_until {
+                Some(spec) => Some(parse_date_spec(&spec, now)?),
+                None => None,
+            };
+            let repeat_on = match repeat_on {
+                Some(spec) => Some(parse_weekdays(&spec)?),
+                None => None,
+            };
+            let repeat = repeat.map(RepeatInterval::validate).transpose()?;
+            let text = match text {
+                Some(text) => {
+                    let (text, truncated) = validate_task_text(text, max_text_length, truncate)?;
+                    if truncated {
+                        eprintln!("Task text cut to {} characters.", max_text_length.unwrap_or_default());
+                    }
+                    Some(text)
+                }
+                None => None,
+            };
+
             if let Some(item) = list.items.get_mut(index) {
                 if let Some(new_text) = text {
                     item.text = new_text;
@@ -385,25 +5659,158 @@ pub fn run_cli() -> Result<()> {
 
                 if clear_repeat {
                     item.repeat_days = None;
-                } else if let Some(new_repeat) = repeat {
-                    item.repeat_days = Some(new_repeat);
+                    item.repeat_spec = None;
+                    item.repeat_preset = None;
+                    item.repeat_until = None;
+                    item.repeat_count = None;
+                    item.due_time = None;
+                    item.skip_weekends = false;
+                } else {
+                    // Switching between interval and weekday repeats replaces the other.
+                    if let Some(interval) = repeat {
+                        item.set_repeat_interval(interval);
+                    }
+                    if let Some(days) = repeat_on {
+                        item.repeat_spec = Some(RepeatSpec::Weekdays(days));
+                        item.repeat_days = None;
+                        item.repeat_preset = None;
+                    }
+                    if repeat_until.is_some() {
+                        item.repeat_until = repeat_until;
+                    }
+                    if repeat_count.is_some() {
+                        item.repeat_count = repeat_count;
+                    }
+                    if clear_time {
+                        item.due_time = None;
+                    } else if due_time.is_some() {
+                        item.due_time = due_time;
+                    }
+                    if skip_weekends || allow_weekends {
+                        item.skip_weekends = skip_weekends;
+                    }
+                }
+
+                if clear_start {
+                    item.start_date = None;
+                } else if start_date.is_some() {
+                    item.start_date = start_date;
+                }
+
+                if clear_due {
+                    item.due_date = None;
+                } else if due_date.is_some() {
+                    item.due_date = due_date;
+                }
+
+                if clear_remind {
+                    item.reminder_lead_minutes = None;
+                } else if reminder_lead.is_some() {
+                    item.reminder_lead_minutes = reminder_lead;
+                }
+
+                if clear_priority {
+                    item.priority = None;
+                } else if priority.is_some() {
+                    item.priority = priority;
+                }
+
+                if clear_notes {
+                    item.notes = None;
+                } else if notes.is_some() {
+                    item.notes = notes;
+                }
+
+                if clear_url {
+                    item.url = None;
+                } else if url.is_some() {
+                    item.url = url;
                 }
 
                 list.save(&path)?;
                 println!("Task {} updated.", index);
             } else {
-                eprintln!("No task with index {}", index);
+                return Err(invalid_input(format!("No task with index {}", index)));
             }
         }
 
-        Commands::Delete { indexes, confirm } => {
-            let mut indices = parse_index_list(&indexes);
+        Commands::Delete {
+            indexes,
+            tag,
+            completed,
+            incomplete,
+            all,
+            force,
+            confirm,
+        } => 'delete: {
+            if force && !all {
+                return Err(invalid_input("--force only applies to delete --all."));
+            }
 
-            if indices.is_empty() {
-                eprintln!("No valid indexes supplied.");
-                return Ok(());
+            if all {
+                let count = list.items.len();
+                if count == 0 {
+                    println!("(nothing to delete)");
+                    return Ok(());
+                }
+
+                if !confirm {
+                    println!("All {} task(s) would be deleted.", count);
+                    println!("\nNothing deleted. Add --confirm to actually delete.");
+                    return Ok(());
+                }
+
+                if !force {
+                    print!("This deletes all {} task(s). Type DELETE to continue: ", count);
+                    std::io::stdout().flush().map_err(TodoError::Io)?;
+                    let mut answer = String::new();
+                    std::io::stdin()
+                    .read_line(&mut answer)
+                    .map_err(TodoError::Io)
+                    .with_context(|| "Failed to read confirmation")?;
+                    if answer.trim() != "DELETE" {
+                        return Err(invalid_input("Confirmation did not match; nothing deleted."));
+                    }
+                }
+
+                list.items.clear();
+                list.save(&path)?;
+                println!("Deleted all {} task(s).", count);
+                break 'delete;
             }
 
+            let has_selectors = tag.is_some() || completed || incomplete;
+
+            let spec = (!indexes.is_empty()).then(|| indexes.join(","));
+            let mut indices = match spec {
+                Some(_) if has_selectors => {
+                    return Err(invalid_input(
+                        "Give either an index list or --tag/--completed/--incomplete, not both.",
+                    ));
+                }
+                Some(spec) => {
+                    let indices = cli_task_list(&spec, &list, now, default_sort)?;
+                    if indices.is_empty() {
+                        return Err(invalid_input("No valid indexes supplied."));
+                    }
+                    indices
+                }
+                None if has_selectors => {
+                    let tag = tag.as_deref().map(normalize_tag);
+                    let indices = list.select(tag.as_deref(), completed, incomplete);
+                    if indices.is_empty() {
+                        println!("(nothing matched)");
+                        return Ok(());
+                    }
+                    indices
+                }
+                None => {
+                    return Err(invalid_input(
+                        "Nothing to delete: give an index list or --tag/--completed/--incomplete.",
+                    ));
+                }
+            };
+
             indices.sort_unstable_by(|a, b| b.cmp(a));
             indices.dedup();
 
@@ -424,20 +5831,521 @@ pub fn run_cli() -> Result<()> {
                 return Ok(());
             }
 
+            confirm_bulk(indices.len(), bulk_threshold, cli.yes)?;
             for idx in &indices {
                 if *idx < list.items.len() {
                     let removed = list.items.remove(*idx);
                     println!("Deleted [{}] {}", idx, removed.text);
                 } else {
-                    eprintln!("Index {} does not exist — skipping.", idx);
+                    eprintln!("Index {} does not exist - skipping.", idx);
+                }
+            }
+
+            list.save(&path)?;
+        }
+
+        Commands::Inbox => {
+            let inbox: Vec<usize> = (0..list.items.len()).filter(|idx| list.items[*idx].in_inbox()).collect();
+            if inbox.is_empty() {
+                println!("The inbox is empty.");
+                return Ok(());
+            }
+
+            let opts = ListOptions {
+                week_start,
+                repeat_display,
+                ascii: cli.ascii,
+                due_windows,
+                iso_dates: cli.iso_dates,
+                date_format: Some(date_format.to_string()),
+                show_inbox: true,
+                ..ListOptions::default()
+            };
+            println!("{}", heading("Inbox", cli.ascii));
+            for idx in inbox {
+                println!("{}", task_line(idx, &list.items[idx], now, &opts));
+            }
+            println!("\n(run `todo triage <index>` with --priority, --tag or --due to file each one)");
+        }
+
+        Commands::Triage {
+            index,
+            priority,
+            tags,
+            due,
+        } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let due = due.map(|spec| parse_date_spec(&spec, now)).transpose()?;
+            let Some(item) = list.items.get_mut(index) else {
+                return Err(invalid_input(format!("No task with index {}", index)));
+            };
+            if !item.remove_tags(&[INBOX_TAG.to_string()]) {
+                return Err(invalid_input(format!("Task {} isn't in the inbox.", index)));
+            }
+            item.add_tags(&tags);
+            if priority.is_some() {
+                item.priority = priority;
+            }
+            if due.is_some() {
+                item.due_date = due;
+            }
+            list.save(&path)?;
+            println!("Task {} triaged.", index);
+        }
+
+        Commands::Tag {
+            indexes,
+            add,
+            remove,
+        } => {
+            if add.is_empty() && remove.is_empty() {
+                return Err(invalid_input(
+                    "Nothing to do: give tags to add and/or --remove <tag>.",
+                ));
+            }
+
+            let mut indices = cli_task_list(&indexes, &list, now, default_sort)?;
+            indices.sort_unstable();
+            indices.dedup();
+
+            let mut changed = 0;
+            for idx in indices {
+                let Some(item) = list.items.get_mut(idx) else {
+                    eprintln!("Index {} does not exist - skipping.", idx);
+                    continue;
+                };
+
+                let added = item.add_tags(&add);
+                let removed = item.remove_tags(&remove);
+                if added || removed {
+                    changed += 1;
+                }
+            }
+
+            if changed > 0 {
+                list.save(&path)?;
+            }
+            println!("{} task(s) changed.", changed);
+        }
+
+        Commands::Random { tag } => {
+            let tag = tag.as_deref().map(normalize_tag);
+            let candidates: Vec<usize> = list
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !item.complete && !item.is_deferred(now) && !item.in_inbox())
+            .filter(|(_, item)| tag.as_ref().is_none_or(|tag| item.tags.contains(tag)))
+            .map(|(idx, _)| idx)
+            .collect();
+
+            if candidates.is_empty() {
+                println!("Nothing to pick from.");
+                return Ok(());
+            }
+            let idx = candidates[fastrand::usize(..candidates.len())];
+            print_task_detail(idx, &list.items[idx], date_format, week_start);
+        }
+
+        Commands::Remind => {
+            // Silence is the point: no headers, no "(none)".
+            for (idx, item) in list.items.iter().enumerate() {
+                let pressing = due_windows.status(item, now).is_some_and(DueStatus::is_pressing);
+                // A reminder's countdown is more precise than "(due today)".
+                let deadline = match item.reminder_countdown(now) {
+                    Some(left) => Some(format!("(due in {})", countdown(left))),
+                    None => due_info(item, now, due_windows, cli.iso_dates).filter(|_| pressing && !item.complete),
+                };
+                if item.is_repeat_due_today(now) || deadline.is_some() {
+                    let due = deadline.map(|due| format!(" {}", due)).unwrap_or_default();
+                    let line = format!("[{}] {}{}", idx, item.text, due);
+                    println!("{}", if cli.ascii { ascii_only(&line) } else { line });
+                }
+            }
+        }
+
+        Commands::Doctor { .. } | Commands::EditFile | Commands::Version | Commands::ReplayLast => {
+            unreachable!("handled before the list is loaded")
+        }
+
+        Commands::History => {
+            let log = SnapshotLog::load(&SnapshotLog::path_for(&path), passphrase)?;
+            if log.snapshots.is_empty() {
+                println!("No snapshots yet; one is saved before each change.");
+                return Ok(());
+            }
+
+            println!("{}", heading("Snapshots (newest first)", cli.ascii));
+            for (n, snapshot) in log.snapshots.iter().rev().enumerate() {
+                println!(
+                    "{:>3}  {}  before {} ({} task(s))",
+                    n + 1,
+                    format_date(snapshot.taken_at, date_format),
+                    snapshot.command,
+                    snapshot.list.items.len()
+                );
+            }
+        }
+
+        Commands::RestoreSnapshot { n } => {
+            let log = SnapshotLog::load(&SnapshotLog::path_for(&path), passphrase)?;
+            let snapshot = log.nth_newest(n).ok_or_else(|| {
+                invalid_input(format!("No snapshot {} (there are {}).", n, log.snapshots.len()))
+            })?;
+
+            list = snapshot.list.clone();
+            list.passphrase = passphrase.cloned();
+            list.migrate()?;
+            list.save(&path)?;
+            println!(
+                "Restored the list from {} ({} task(s)).",
+                format_date(snapshot.taken_at, date_format),
+                list.items.len()
+            );
+        }
+
+        Commands::Stats { json } => {
+            let stats = list.stats(now, &config.priority_weights);
+            if json {
+                let json = serde_json::to_string_pretty(&stats).with_context(|| "Failed to serialize JSON")?;
+                println!("{}", json);
+            } else {
+                print_list_stats(&stats, cli.ascii);
+            }
+        }
+
+        Commands::Diff { other } => {
+            if !other.exists() {
+                return Err(invalid_input(format!("No such list file: {}", other.display())));
+            }
+            // Reset the other copy the same way, so only real differences show.
+            let mut other_list = TodoList::read_only(&other, passphrase)?;
+            other_list.auto_reset_repeating(now, !cli.no_catchup);
+            print_list_diff(&list, &other_list, &other, date_format);
+        }
+
+        Commands::StatsTask { index } => {
+            let index = index.resolve(&list, now, default_sort)?;
+            let item = list
+            .items
+            .get(index)
+            .ok_or_else(|| invalid_input(format!("No task with index {}", index)))?;
+            print_task_stats(index, item, now, date_format, cli.ascii);
+        }
+
+        Commands::Merge {
+            into,
+            from,
+            confirm,
+        } => {
+            let into = into.resolve(&list, now, default_sort)?;
+            if into >= list.items.len() {
+                return Err(invalid_input(format!("No task with index {}", into)));
+            }
+
+            let mut sources = Vec::new();
+            for idx in cli_task_list(&from, &list, now, default_sort)? {
+                if idx == into {
+                    eprintln!("Index {} is the merge target - skipping.", idx);
+                } else if idx >= list.items.len() {
+                    eprintln!("Index {} does not exist - skipping.", idx);
+                } else if !sources.contains(&idx) {
+                    sources.push(idx);
+                }
+            }
+
+            if sources.is_empty() {
+                return Err(invalid_input("No valid indexes to merge from."));
+            }
+
+            // Highest first so removals don't shift the indexes still to go.
+            sources.sort_unstable_by(|a, b| b.cmp(a));
+
+            if !confirm {
+                println!(
+                    "The following tasks would be merged into [{}] {} and deleted (run again with --confirm to proceed):\n",
+                    into, list.items[into].text
+                );
+                for idx in &sources {
+                    println!("[{}] {}", idx, list.items[*idx].text);
+                }
+                println!("\nNothing merged. Add --confirm to actually merge.");
+                return Ok(());
+            }
+
+            for idx in sources.iter().rev() {
+                let source = list.items[*idx].clone();
+                list.items[into].merge_from(&source);
+            }
+            for idx in &sources {
+                list.items.remove(*idx);
+            }
+
+            let new_index = into - sources.iter().filter(|idx| **idx < into).count();
+            list.save(&path)?;
+
+            let merged = &list.items[new_index];
+            let state = if merged.complete { "complete" } else { "incomplete" };
+            println!("Merged {} task(s) into:", sources.len());
+            println!("[{}] {}{} ({})", new_index, merged.text, tag_info(merged), state);
+            if let Some(notes) = &merged.notes {
+                println!("    notes: {}", notes.replace('\n', "\n           "));
+            }
+        }
+
+        Commands::Dedupe {
+            ignore_case,
+            confirm,
+        } => {
+            let groups = list.duplicate_groups(ignore_case);
+            if groups.is_empty() {
+                println!("No duplicate tasks found.");
+                return Ok(());
+            }
+
+            let mut doomed: Vec<usize> = groups.iter().flat_map(|group| group[1..].iter().copied()).collect();
+            if !confirm {
+                println!("Found {} group(s) of duplicates (run again with --confirm to remove them):\n", groups.len());
+                for group in &groups {
+                    let keep = &list.items[group[0]];
+                    println!("keep   [{}] {}{}", group[0], keep.text, tag_info(keep));
+                    for idx in &group[1..] {
+                        println!("remove [{}] {}{}", idx, list.items[*idx].text, tag_info(&list.items[*idx]));
+                    }
+                    println!();
+                }
+                println!("Nothing removed. Add --confirm to remove {} task(s).", doomed.len());
+                return Ok(());
+            }
+
+            doomed.sort_unstable_by(|a, b| b.cmp(a));
+            for idx in &doomed {
+                list.items.remove(*idx);
+            }
+            list.save(&path)?;
+            println!("Removed {} duplicate task(s) from {} group(s).", doomed.len(), groups.len());
+        }
+
+        Commands::Replace {
+            from,
+            to,
+            ignore_case,
+            confirm,
+        } => {
+            if from.is_empty() {
+                return Err(invalid_input("Nothing to replace: the text to find is empty."));
+            }
+
+            let changes: Vec<(usize, String)> = list
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| Some((idx, replace_text(&item.text, &from, &to, ignore_case)?)))
+            .collect();
+            if changes.is_empty() {
+                println!("No task text contains \"{}\".", from);
+                return Ok(());
+            }
+
+            if !confirm {
+                println!("{} task(s) would change (run again with --confirm to apply):\n", changes.len());
+                for (idx, text) in &changes {
+                    println!("[{}] {}", idx, list.items[*idx].text);
+                    println!("  -> {}", text);
+                }
+                println!("\nNothing changed. Add --confirm to apply.");
+                return Ok(());
+            }
+
+            let count = changes.len();
+            for (idx, text) in changes {
+                list.items[idx].text = text;
+            }
+            list.save(&path)?;
+            println!("Replaced text in {} task(s).", count);
+        }
+
+        Commands::Search {
+            query,
+            limit,
+            count,
+            print0,
+        } => {
+            let hits = list.search(&query);
+            if count {
+                println!("{}", hits.len());
+                return Ok(());
+            }
+            if hits.is_empty() && !print0 {
+                println!("No tasks match \"{}\".", query);
+                return Ok(());
+            }
+
+            let color = color_enabled(cli.no_color) && !print0;
+            for idx in hits.iter().take(limit) {
+                let item = &list.items[*idx];
+                let text = if color {
+                    highlight(&item.text, &query)
+                } else {
+                    item.text.clone()
+                };
+                let status = if item.complete { "x" } else { " " };
+                let line = format!("[{}] [{}] {}{}", idx, status, text, tag_info(item));
+                let line = if cli.ascii { ascii_only(&line) } else { line };
+                match print0 {
+                    true => print!("{}\0", line),
+                    false => println!("{}", line),
                 }
             }
 
+            if hits.len() > limit && !print0 {
+                let more = format!("({} more — use --limit to see them)", hits.len() - limit);
+                println!("{}", if cli.ascii { ascii_only(&more) } else { more });
+            }
+        }
+
+        Commands::Export {
+            format,
+            computed,
+            to,
+            force,
+            to_clipboard,
+        } => {
+            let format = match (format, &to) {
+                (Some(format), _) => format,
+                (None, Some(target)) => ExportFormat::from_path(target).ok_or_else(|| {
+                    invalid_input(format!(
+                        "Can't tell the format of {}; pass --format json|ron|markdown|todotxt",
+                        target.display()
+                    ))
+                })?,
+                (None, None) => ExportFormat::Json,
+            };
+            if computed && format != ExportFormat::Json {
+                eprintln!("--computed only applies to JSON; exporting stored fields.");
+            }
+            let contents = match format {
+                ExportFormat::Json => format!("{}\n", list.to_json(now, computed)?),
+                ExportFormat::Ron => format!("{}\n", list.to_ron()?),
+                ExportFormat::Markdown => list.to_markdown(),
+                ExportFormat::Todotxt => list.to_todotxt(),
+            };
+
+            if to_clipboard {
+                let bytes = contents.len();
+                set_clipboard_text(contents)?;
+                println!("Copied {} byte(s) to the clipboard.", bytes);
+                return Ok(());
+            }
+            let Some(target) = to else {
+                print!("{}", contents);
+                return Ok(());
+            };
+            if target.exists() && !force {
+                return Err(invalid_input(format!(
+                    "{} already exists; pass --force to overwrite it.",
+                    target.display()
+                )));
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                .map_err(TodoError::Io)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
+            fs::write(&target, contents)
+            .map_err(TodoError::Io)
+            .with_context(|| format!("Failed to write export: {}", target.display()))?;
+            println!("Exported {} task(s) to {}.", list.items.len(), target.display());
+        }
+
+        Commands::Import {
+            path: source,
+            format,
+            replace,
+        } => {
+            let format = format
+            .or_else(|| ImportFormat::from_path(&source))
+            .ok_or_else(|| {
+                invalid_input(format!(
+                    "Can't tell the format of {}; pass --format json|markdown|todotxt",
+                    source.display()
+                ))
+            })?;
+
+            let contents = fs::read_to_string(&source)
+            .map_err(TodoError::Io)
+            .with_context(|| format!("Failed to read file: {}", source.display()))?;
+
+            let (items, unrecognized) = match format {
+                ImportFormat::Json => {
+                    let mut imported: TodoList = serde_json::from_str(&contents)
+                    .map_err(|err| invalid_input(format!("Invalid JSON import: {}", err)))?;
+                    imported.migrate()?;
+                    (imported.items, 0)
+                }
+                ImportFormat::Markdown => parse_markdown_tasks(&contents, now),
+                ImportFormat::Todotxt => parse_todotxt_tasks(&contents, now),
+            };
+
+            let count = items.len();
+            let duplicates = list.import(items, replace);
             list.save(&path)?;
+
+            if replace {
+                println!("Replaced list with {} imported task(s).", count);
+            } else {
+                println!(
+                    "Imported {} task(s), skipped {} duplicate(s).",
+                    count - duplicates,
+                    duplicates
+                );
+            }
+            if unrecognized > 0 {
+                println!("Skipped {} unrecognized line(s).", unrecognized);
+            }
+        }
+
+        #[cfg(feature = "tui")]
+        Commands::Tui => {
+            tui::run(list, &path, now)?;
+        }
+
+        Commands::Watch => {
+            let opts = ListOptions {
+                week_start,
+                theme,
+                repeat_display,
+                ascii: cli.ascii,
+                due_windows,
+                iso_dates: cli.iso_dates,
+                date_format: Some(date_format.to_string()),
+                ..ListOptions::default()
+            };
+            watch_list(&path, &opts, passphrase, !cli.no_catchup)?;
         }
 
         Commands::Commands => {
-            print_command_table();
+            print_command_table(cli.ascii);
+        }
+    }
+
+    // Only once a change has gone through, so the archiving is snapshotted
+    // and committed with it, and failed or read-only commands never write.
+    if let Some(days) = config.auto_archive_days.filter(|days| *days > 0 && mutation.is_some()) {
+        auto_archive(&path, passphrase, now, days)?;
+    }
+    if let (Some(before), Some(label)) = (before, mutation) {
+        record_snapshot(&path, before, label, now, !cli.no_catchup)?;
+    }
+    if let Some(message) = git_message {
+        git_commit(&path, message)?;
+    }
+    if config.show_summary_on_exit && mutation.is_some() && !cli.quiet {
+        // Reloaded: some commands hand the list off (`tui`) or save a copy.
+        let week = now.date_naive().week(week_start.weekday()).first_day();
+        if let Some(since) = local_at(week, NaiveTime::MIN) {
+            let (added, completed) = TodoList::load(&path, passphrase)?.activity_since(since);
+            println!("{} added, {} completed this week", added, completed);
         }
     }
 
@@ -1,5 +1,18 @@
-use anyhow::Result;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    todo_ron_cli::run_cli()
+use todo_ron_cli::TodoError;
+
+fn main() -> ExitCode {
+    match todo_ron_cli::run_cli() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Usage errors already carry clap's formatted message and hints.
+            if let Some(TodoError::Usage(usage)) = err.downcast_ref::<TodoError>() {
+                let _ = usage.print();
+            } else {
+                eprintln!("Error: {:#}", err);
+            }
+            ExitCode::from(todo_ron_cli::exit_code(&err))
+        }
+    }
 }
@@ -0,0 +1,144 @@
+//! Interactive terminal UI (`todo tui`), built only with the `tui` feature.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::PathBuf;
+
+use crate::{tag_info, TodoList};
+
+enum Mode {
+    Browse,
+    /// Editing the selected task's text; holds the text typed so far.
+    Edit(String),
+}
+
+struct App {
+    list: TodoList,
+    state: ListState,
+    mode: Mode,
+    now: DateTime<Local>,
+}
+
+/// Run the TUI on an already loaded (and auto-reset) list, saving it to
+/// `path` when the user quits with `q`.
+pub fn run(list: TodoList, path: &PathBuf, now: DateTime<Local>) -> Result<()> {
+    let mut app = App {
+        state: ListState::default().with_selected(if list.items.is_empty() { None } else { Some(0) }),
+        list,
+        mode: Mode::Browse,
+        now,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result?;
+
+    app.list.save(path)
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal
+            .draw(|frame| self.draw(frame))
+            .with_context(|| "Failed to draw TUI")?;
+
+            let Event::Key(key) = event::read().with_context(|| "Failed to read terminal event")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match &mut self.mode {
+                Mode::Browse => match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+                    KeyCode::Down | KeyCode::Char('j') => self.select_next(),
+                    KeyCode::Char(' ') => self.toggle_selected(),
+                    KeyCode::Char('d') => self.delete_selected(),
+                    KeyCode::Char('e') => {
+                        if let Some(item) = self.selected_index().map(|idx| &self.list.items[idx]) {
+                            self.mode = Mode::Edit(item.text.clone());
+                        }
+                    }
+                    _ => {}
+                },
+                Mode::Edit(buffer) => match key.code {
+                    KeyCode::Enter => {
+                        let text = buffer.trim().to_string();
+                        if let (Some(idx), false) = (self.selected_index(), text.is_empty()) {
+                            self.list.items[idx].text = text;
+                        }
+                        self.mode = Mode::Browse;
+                    }
+                    KeyCode::Esc => self.mode = Mode::Browse,
+                    KeyCode::Backspace => {
+                        buffer.pop();
+                    }
+                    KeyCode::Char(c) => buffer.push(c),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        self.state.selected().filter(|idx| *idx < self.list.items.len())
+    }
+
+    fn select_next(&mut self) {
+        if let Some(idx) = self.state.selected() {
+            if idx + 1 < self.list.items.len() {
+                self.state.select(Some(idx + 1));
+            }
+        }
+    }
+
+    fn toggle_selected(&mut self) {
+        if let Some(idx) = self.selected_index() {
+            self.list.items[idx].toggle(self.now);
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        if let Some(idx) = self.selected_index() {
+            self.list.items.remove(idx);
+            let len = self.list.items.len();
+            self.state.select(if len == 0 { None } else { Some(idx.min(len - 1)) });
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [body, footer] = Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+        .list
+        .items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let status = if item.complete { "[x]" } else { "[ ]" };
+            ListItem::new(format!("{} [{}] {}{}", status, idx, item.text, tag_info(item)))
+        })
+        .collect();
+
+        let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Todoster "))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.state);
+
+        let help = match &self.mode {
+            Mode::Browse => Line::from("↑/↓ move · space toggle · d delete · e edit · q save & quit"),
+            Mode::Edit(buffer) => Line::from(format!("Edit: {}_   (Enter save · Esc cancel)", buffer)),
+        };
+        frame.render_widget(Paragraph::new(help).block(Block::default().borders(Borders::ALL)), footer);
+    }
+}
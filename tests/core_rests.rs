@@ -1,7 +1,75 @@
-use chrono::{Duration, Local};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use chrono::{Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
 
 use todo_ron_cli::*; // <-- import from the crate root
 
+/// A scratch directory for driving the `todo` binary. It holds the data
+/// file and stands in for XDG_CONFIG_HOME and XDG_DATA_HOME, so the
+/// developer's own config never changes what a test sees. Dropping it
+/// removes everything, snapshots and view state included.
+struct Sandbox {
+    dir: PathBuf,
+    file: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("todoster-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Sandbox { file: dir.join("todos.ron"), dir }
+    }
+
+    /// `todo` with the sandbox's config and data directories, no key and
+    /// no stdin, but without `--file`.
+    fn bare(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_todo"));
+        command
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env_remove("TODOSTER_KEY")
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// `todo --file <the sandbox's data file>`, ready for more arguments.
+    fn command(&self) -> Command {
+        let mut command = self.bare();
+        command.arg("--file").arg(&self.file);
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().unwrap()
+    }
+
+    fn stdout(&self, args: &[&str]) -> String {
+        String::from_utf8(self.run(args).stdout).unwrap()
+    }
+
+    fn write_config(&self, config: &str) {
+        let dir = self.dir.join("config/todoster");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.ron"), config).unwrap();
+    }
+
+    fn load(&self) -> TodoList {
+        ron::from_str(&std::fs::read_to_string(&self.file).unwrap()).unwrap()
+    }
+
+    fn store(&self, list: &TodoList) {
+        std::fs::write(&self.file, ron::to_string(list).unwrap()).unwrap();
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
 #[test]
 fn parse_index_list_handles_single_indexes() {
     let indices = parse_index_list("0,2,4");
@@ -40,7 +108,7 @@ fn repeating_task_resets_after_due_time() {
     item.mark_complete(now - Duration::days(3));
     item.reset_if_due(now);
 
-    assert_eq!(item.complete, false);
+    assert!(!item.complete);
     assert!(item.complete_date.is_none());
 }
 
@@ -52,6 +120,2082 @@ fn non_repeating_task_does_not_reset() {
     item.mark_complete(now - Duration::days(10));
     item.reset_if_due(now);
 
-    assert_eq!(item.complete, true);
+    assert!(item.complete);
     assert!(item.complete_date.is_some());
 }
+
+#[test]
+fn parse_date_spec_handles_relative_and_absolute_dates() {
+    let now = Local.with_ymd_and_hms(2026, 1, 14, 15, 30, 0).unwrap(); // a Wednesday
+    let midnight = |y, m, d| Local.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+
+    assert_eq!(parse_date_spec("today", now).unwrap(), midnight(2026, 1, 14));
+    assert_eq!(parse_date_spec("tomorrow", now).unwrap(), midnight(2026, 1, 15));
+    assert_eq!(parse_date_spec("+3d", now).unwrap(), midnight(2026, 1, 17));
+    assert_eq!(parse_date_spec("2w", now).unwrap(), midnight(2026, 1, 28));
+    assert_eq!(parse_date_spec("friday", now).unwrap(), midnight(2026, 1, 16));
+    assert_eq!(parse_date_spec("wed", now).unwrap(), midnight(2026, 1, 21));
+    assert_eq!(parse_date_spec("2026-02-01", now).unwrap(), midnight(2026, 2, 1));
+    assert!(parse_date_spec("someday", now).is_err());
+    // Odd input is rejected, never a panic.
+    assert!(parse_date_spec("3é", now).is_err());
+    assert!(parse_date_spec("é", now).is_err());
+    assert!(parse_date_spec("+99999999999999w", now).is_err());
+}
+
+#[test]
+fn task_is_deferred_only_until_start_date() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Plan holiday".into(), None);
+    assert!(!item.is_deferred(now));
+
+    item.start_date = Some(now + Duration::days(2));
+    assert!(item.is_deferred(now));
+    assert!(!item.is_deferred(now + Duration::days(2)));
+}
+
+#[test]
+fn tags_are_normalized_and_deduplicated() {
+    let mut item = TodoItem::new("File taxes".into(), None);
+
+    assert!(item.add_tags(&["#finance".into(), "home".into(), "finance".into()]));
+    assert_eq!(item.tags, vec!["finance", "home"]);
+
+    assert!(!item.add_tags(&["home".into()]));
+    assert!(item.remove_tags(&["#home".into()]));
+    assert_eq!(item.tags, vec!["finance"]);
+}
+
+#[test]
+fn exit_code_follows_error_class_through_context() {
+    let invalid = anyhow::Error::from(TodoError::InvalidInput("No task with index 9".into()))
+        .context("while completing");
+    assert_eq!(exit_code(&invalid), 4);
+
+    let io = anyhow::Error::from(TodoError::Io(std::io::Error::other("disk full")));
+    assert_eq!(exit_code(&io), 2);
+
+    assert_eq!(exit_code(&anyhow::anyhow!("something else")), 1);
+}
+
+#[test]
+fn repeat_stops_once_count_is_exhausted() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Water seedlings".into(), Some(1));
+    item.repeat_count = Some(1);
+
+    item.mark_complete(now - Duration::days(2));
+    item.reset_if_due(now);
+    assert!(!item.complete);
+    assert_eq!(item.repeat_count, Some(0));
+
+    item.mark_complete(now - Duration::days(2));
+    item.reset_if_due(now);
+    assert!(item.complete);
+    assert!(item.repeat_finished());
+    assert!(item.time_until_next_repeat(now).is_none());
+}
+
+#[test]
+fn repeat_stops_after_until_date() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Water seedlings".into(), Some(1));
+    item.repeat_until = Some(now - Duration::days(3));
+
+    // Completed two days ago, so the next repeat (yesterday) falls after the end date.
+    item.mark_complete(now - Duration::days(2));
+    item.reset_if_due(now);
+    assert!(item.complete);
+    assert!(item.repeat_finished());
+
+    // Still within the window: the repeat happens as normal.
+    item.repeat_until = Some(now + Duration::days(3));
+    item.reset_if_due(now);
+    assert!(!item.complete);
+}
+
+#[test]
+fn merge_unions_tags_and_keeps_most_complete_state() {
+    let now = Local::now();
+    let mut keep = TodoItem::new("Pay rent".into(), None);
+    keep.tags = vec!["home".into()];
+    keep.notes = Some("from phone".into());
+    keep.created_at = Some(now - Duration::days(1));
+
+    let mut dup = TodoItem::new("Pay rent".into(), None);
+    dup.tags = vec!["finance".into(), "home".into()];
+    dup.notes = Some("from laptop".into());
+    dup.created_at = Some(now - Duration::days(5));
+    dup.mark_complete(now);
+
+    keep.merge_from(&dup);
+
+    assert_eq!(keep.tags, vec!["home", "finance"]);
+    assert_eq!(keep.notes.as_deref(), Some("from phone\nfrom laptop"));
+    assert_eq!(keep.created_at, Some(now - Duration::days(5)));
+    assert!(keep.complete);
+    assert_eq!(keep.complete_date, Some(now));
+}
+
+#[test]
+fn completion_history_tracks_gaps_between_completions() {
+    let start = Local::now() - Duration::days(10);
+    let mut item = TodoItem::new("Feed gecko".into(), Some(2));
+
+    for offset in [0, 2, 5, 7] {
+        item.mark_complete(start + Duration::days(offset));
+    }
+    assert_eq!(item.completion_history.len(), 4);
+    assert_eq!(item.completion_gaps_days(), vec![2.0, 3.0, 2.0]);
+
+    let (mean, median) = item.completion_gap_stats().unwrap();
+    assert!((mean - 7.0 / 3.0).abs() < 1e-9);
+    assert_eq!(median, 2.0);
+
+    // Undo drops the completion it reverses.
+    item.mark_incomplete();
+    assert_eq!(item.completion_history.len(), 3);
+}
+
+#[test]
+fn repeat_due_today_covers_pending_and_overdue_repeats() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Feed gecko".into(), Some(2));
+    assert!(item.is_repeat_due_today(now));
+
+    item.mark_complete(now);
+    assert!(!item.is_repeat_due_today(now));
+
+    item.mark_complete(now - Duration::days(3));
+    assert!(item.is_repeat_due_today(now));
+
+    let one_off = TodoItem::new("One-off task".into(), None);
+    assert!(!one_off.is_repeat_due_today(now));
+}
+
+#[test]
+fn computed_export_fields_derive_from_dates() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Feed gecko".into(), Some(2));
+    item.created_at = Some(now - Duration::days(10));
+    item.mark_complete(now - Duration::days(5));
+
+    let computed = ComputedItem::new(&item, now);
+    assert_eq!(computed.overdue_days, Some(3));
+    assert_eq!(computed.age_days, Some(10));
+
+    let fresh = TodoItem::new("One-off task".into(), None);
+    let computed = ComputedItem::new(&fresh, now);
+    assert_eq!(computed.overdue_days, None);
+    assert_eq!(computed.age_days, None);
+}
+
+#[test]
+fn search_ranks_word_matches_before_substrings_then_shorter_first() {
+    let mut list = TodoList::default();
+    for text in ["Buy geckofood", "Feed the gecko and mist tank", "Call mum", "GECKO vet"] {
+        list.items.push(TodoItem::new(text.into(), None));
+    }
+
+    assert_eq!(list.search("gecko"), vec![3, 1, 0]);
+    assert!(list.search("   ").is_empty());
+}
+
+#[test]
+fn markdown_import_uses_checkbox_state_and_counts_skipped_lines() {
+    let now = Local::now();
+    let input = "## Complete\n- [ ] still to do\n- [x] done\nnot a task\n\n* [X] also done\n";
+
+    let (items, skipped) = parse_markdown_tasks(input, now);
+
+    assert_eq!(skipped, 1);
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].text, "still to do");
+    assert!(!items[0].complete);
+    assert!(items[1].complete);
+    assert_eq!(items[1].complete_date, Some(now));
+    assert!(items[2].complete);
+}
+
+#[test]
+fn todotxt_import_and_export_round_trip() {
+    let now = Local::now();
+    let midnight = |y, m, d| Local.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+    let input = "(A) 2026-03-01 Call Mom +family @phone due:2026-03-10\n\
+                 x 2026-03-05 2026-03-01 Pay rent +finance pri:B\n\
+                 Water plants rec:3d\n\
+                 \n\
+                 (C) +tag-only\n";
+
+    let (items, skipped) = parse_todotxt_tasks(input, now);
+
+    assert_eq!(skipped, 1);
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].text, "Call Mom");
+    assert_eq!(items[0].priority, Some(Priority::High));
+    assert_eq!(items[0].tags, vec!["family", "@phone"]);
+    assert_eq!(items[0].created_at, Some(midnight(2026, 3, 1)));
+    assert_eq!(items[0].due_date, Some(midnight(2026, 3, 10)));
+    assert!(items[1].complete);
+    assert_eq!(items[1].complete_date, Some(midnight(2026, 3, 5)));
+    assert_eq!(items[1].priority, Some(Priority::Medium));
+    assert_eq!(items[2].repeat_days, Some(3));
+    assert_eq!(items[2].created_at, Some(now));
+
+    let list = TodoList {
+        items,
+        ..TodoList::default()
+    };
+    let exported = list.to_todotxt();
+    let lines: Vec<&str> = exported.lines().collect();
+    assert_eq!(lines[0], "(A) 2026-03-01 Call Mom +family @phone due:2026-03-10");
+    assert_eq!(lines[1], "x 2026-03-05 2026-03-01 Pay rent +finance pri:B");
+    assert!(lines[2].ends_with(" Water plants rec:3d"), "{}", exported);
+}
+
+#[test]
+fn skip_weekends_moves_saturday_and_sunday_repeats_to_monday() {
+    let at = |d| Local.with_ymd_and_hms(2026, 1, d, 9, 0, 0).unwrap();
+    let mut item = TodoItem::new("Check build server".into(), Some(3));
+    item.skip_weekends = true;
+
+    // Wednesday + 3 days is Saturday the 17th.
+    item.mark_complete(at(14));
+    assert_eq!(item.next_due_start(), Some(at(19)));
+    // Thursday + 3 days is Sunday the 18th.
+    item.mark_complete(at(15));
+    assert_eq!(item.next_due_start(), Some(at(19)));
+    // Friday + 3 days is already a Monday.
+    item.mark_complete(at(16));
+    assert_eq!(item.next_due_start(), Some(at(19)));
+
+    item.skip_weekends = false;
+    item.mark_complete(at(14));
+    assert_eq!(item.next_due_start(), Some(at(17)));
+}
+
+#[test]
+fn sunday_repeat_lands_on_next_sunday_across_week_boundary() {
+    let midnight = |y, m, d| Local.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+    let mut item = TodoItem::new("Weekly review".into(), None);
+    item.repeat_spec = Some(RepeatSpec::Weekdays(vec![Weekday::Sun]));
+
+    // Saturday evening: due the very next day.
+    item.mark_complete(Local.with_ymd_and_hms(2026, 1, 17, 20, 0, 0).unwrap());
+    assert_eq!(item.next_due_start(), Some(midnight(2026, 1, 18)));
+
+    // Done on the Sunday itself: due a week later, not the same day.
+    item.mark_complete(Local.with_ymd_and_hms(2026, 1, 18, 9, 0, 0).unwrap());
+    assert_eq!(item.next_due_start(), Some(midnight(2026, 1, 25)));
+    assert!(item.should_reset(midnight(2026, 1, 25)));
+    assert!(!item.should_reset(midnight(2026, 1, 24)));
+}
+
+#[test]
+fn start_of_week_depends_on_week_start_setting() {
+    let saturday = NaiveDate::from_ymd_opt(2026, 1, 17).unwrap();
+    let sunday = NaiveDate::from_ymd_opt(2026, 1, 18).unwrap();
+    let date = |d| NaiveDate::from_ymd_opt(2026, 1, d).unwrap();
+
+    assert_eq!(WeekStart::Monday.start_of_week(saturday), date(12));
+    assert_eq!(WeekStart::Monday.start_of_week(sunday), date(12));
+    assert_eq!(WeekStart::Sunday.start_of_week(saturday), date(11));
+    assert_eq!(WeekStart::Sunday.start_of_week(sunday), date(18));
+}
+
+#[test]
+fn skipping_moves_schedule_without_recording_completion() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Gym".into(), Some(2));
+
+    // Due now: skipping acts like completing now.
+    assert!(item.skip_occurrence(now));
+    assert!(item.complete);
+    assert_eq!(item.next_due_start(), Some(now + Duration::days(2)));
+
+    // Already complete: skip the upcoming occurrence too.
+    assert!(item.skip_occurrence(now));
+    assert_eq!(item.next_due_start(), Some(now + Duration::days(4)));
+    assert!(item.completion_history.is_empty());
+
+    let mut one_off = TodoItem::new("One-off task".into(), None);
+    assert!(!one_off.skip_occurrence(now));
+    assert!(!one_off.complete);
+}
+
+#[test]
+fn hourly_repeats_count_from_the_completion_time() {
+    assert_eq!("4h".parse(), Ok(RepeatInterval::Hours(4)));
+    assert_eq!("3".parse(), Ok(RepeatInterval::Days(3)));
+    assert!("4x".parse::<RepeatInterval>().is_err());
+    let err = RepeatInterval::Hours(0).validate().unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+    assert!(err.to_string().contains("at least 1 hour"), "{}", err);
+
+    let at = |h, m| Local.with_ymd_and_hms(2026, 1, 14, h, m, 0).unwrap();
+    let mut item = TodoItem::new("Stretch".to_string(), Some(2));
+    item.set_repeat_interval(RepeatInterval::Hours(4));
+    assert_eq!(item.repeat_days, None);
+    // A due time only applies to day-based repeats.
+    item.due_time = Some(NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+    item.mark_complete(at(10, 0));
+
+    assert_eq!(item.next_due_start(), Some(at(14, 0)));
+    assert_eq!(item.time_until_next_repeat(at(12, 30)), Some(Duration::minutes(90)));
+    assert!(!item.should_reset(at(13, 59)));
+    assert!(item.should_reset(at(14, 0)));
+    assert_eq!(item.missed_occurrences(at(22, 30)), 2);
+}
+
+#[test]
+fn repeat_presets_map_onto_schedules() {
+    let weekdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+    let cases = [
+        ("daily", RepeatPreset::Daily, Some(1), None),
+        ("weekly", RepeatPreset::Weekly, Some(7), None),
+        ("Fortnightly", RepeatPreset::Fortnightly, Some(14), None),
+        ("monthly", RepeatPreset::Monthly, None, Some(RepeatSpec::Months(1))),
+        ("weekday", RepeatPreset::Weekday, None, Some(RepeatSpec::Weekdays(weekdays))),
+    ];
+    for (name, preset, days, spec) in cases {
+        let interval: RepeatInterval = name.parse().unwrap();
+        assert_eq!(interval, RepeatInterval::Preset(preset));
+        let mut item = TodoItem::new("Chore".to_string(), Some(3));
+        item.set_repeat_interval(interval);
+        assert_eq!((item.repeat_days, item.repeat_spec), (days, spec), "{}", name);
+        assert_eq!(item.repeat_preset, Some(preset));
+        assert_eq!(preset.name(), name.to_lowercase());
+    }
+
+    // Month-aware: the 31st lands on the last day of a shorter month.
+    let mut rent = TodoItem::new("Pay rent".to_string(), None);
+    rent.set_repeat_interval(RepeatInterval::Preset(RepeatPreset::Monthly));
+    rent.mark_complete(Local.with_ymd_and_hms(2026, 1, 31, 9, 0, 0).unwrap());
+    assert_eq!(rent.next_due_start(), Some(Local.with_ymd_and_hms(2026, 2, 28, 9, 0, 0).unwrap()));
+
+    // A plain interval forgets the preset.
+    rent.set_repeat_interval(RepeatInterval::Days(30));
+    assert_eq!((rent.repeat_spec, rent.repeat_preset), (None, None));
+}
+
+#[test]
+fn task_ids_are_stable_and_resolve_from_hash_refs() {
+    let now = Local.with_ymd_and_hms(2026, 1, 14, 10, 0, 0).unwrap();
+    let mut list: TodoList = ron::from_str(
+        r#"(items: [(text: "Feed gecko", complete: false, complete_date: None, repeat_days: None),
+                    (text: "Read", complete: false, complete_date: None, repeat_days: None)])"#,
+    )
+    .unwrap();
+    list.assign_ids();
+    let ids: Vec<u64> = list.items.iter().map(|item| item.id).collect();
+    assert_eq!(ids, [1, 2]);
+
+    // Deleting doesn't free an id, and imported duplicates get fresh ones.
+    list.items.remove(1);
+    list.import(vec![list.items[0].clone(), TodoItem::new("Nap".to_string(), None)], false);
+    let ids: Vec<u64> = list.items.iter().map(|item| item.id).collect();
+    assert_eq!(ids, [1, 3]);
+
+    assert_eq!("#3".parse(), Ok(TaskRef::Id(3)));
+    assert_eq!(TaskRef::Id(3).resolve(&list, now, None).unwrap(), 1);
+    let err = TaskRef::Id(2).resolve(&list, now, None).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+    assert!(err.to_string().contains("#2"), "{}", err);
+}
+
+#[test]
+fn non_positive_repeat_intervals_are_rejected() {
+    assert_eq!(validate_repeat_days(1).unwrap(), 1);
+
+    for days in [0, -3] {
+        let err = validate_repeat_days(days).unwrap_err();
+        assert_eq!(exit_code(&err), 4);
+        assert!(err.to_string().contains("at least 1 day"), "{}", err);
+    }
+}
+
+#[test]
+fn cli_rejects_repeat_zero_and_negative() {
+    let todo = Sandbox::new("repeat");
+
+    for days in ["0", "-3"] {
+        let output = todo.run(&["add", "Feed gecko", "--repeat", days]);
+
+        assert_eq!(output.status.code(), Some(4));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Repeat interval must be at least 1 day"), "{}", stderr);
+    }
+    assert!(!todo.file.exists());
+}
+
+#[test]
+fn themes_resolve_by_list_name_or_flag() {
+    let config: Config = ron::from_str(
+        r#"(themes: {"work": (header: Some(Blue), accent: Some(Yellow)), "home": (header: Some(Green))})"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.theme_for("work", None).unwrap().accent, Some(AnsiColor::Yellow));
+    assert_eq!(config.theme_for("todos", None).unwrap(), Theme::default());
+    assert_eq!(config.theme_for("work", Some("home")).unwrap().header, Some(AnsiColor::Green));
+
+    let err = config.theme_for("work", Some("missing")).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+    assert!(err.to_string().contains("home, work"), "{}", err);
+}
+
+#[test]
+fn completion_note_is_cleared_when_task_resets() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Run".to_string(), Some(1));
+    item.mark_complete(now - Duration::days(2));
+    item.last_completion_note = Some("5k in the rain".to_string());
+
+    item.reset_if_due(now - Duration::days(1) - Duration::hours(1));
+    assert_eq!(item.last_completion_note.as_deref(), Some("5k in the rain"));
+
+    item.reset_if_due(now);
+    assert!(!item.complete);
+    assert_eq!(item.last_completion_note, None);
+}
+
+#[test]
+fn cli_delete_all_requires_typed_confirmation() {
+    use std::io::Write;
+
+    let sandbox = Sandbox::new("delete-all");
+    let todo = |args: &[&str], input: &str| {
+        let mut child = sandbox
+            .command()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait().unwrap().code()
+    };
+    let remaining = || sandbox.load().items.len();
+
+    assert_eq!(todo(&["add", "Feed gecko"], ""), Some(0));
+    assert_eq!(todo(&["delete", "--all", "--confirm"], "delete\n"), Some(4));
+    assert_eq!(remaining(), 1);
+
+    assert_eq!(todo(&["delete", "--all", "--confirm"], "DELETE\n"), Some(0));
+    assert_eq!(remaining(), 0);
+}
+
+#[test]
+fn parse_index_list_checked_reports_malformed_parts() {
+    assert_eq!(parse_index_list_checked("0, 2-3,,5"), Ok(vec![0, 2, 3, 5]));
+    assert_eq!(
+        parse_index_list_checked("1,,3x,2-,abc,4"),
+        Err(vec!["3x".to_string(), "2-".to_string(), "abc".to_string()])
+    );
+    // The lenient version keeps what it can.
+    assert_eq!(parse_index_list("1,,3x,2-,abc,4"), vec![1, 4]);
+}
+
+#[test]
+fn progress_completes_at_target_and_restarts_on_repeat() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Read".to_string(), Some(1));
+    item.target = Some(10);
+
+    assert!(!item.add_progress(4, now - Duration::days(2)));
+    assert_eq!(item.progress, 4);
+    assert!(item.add_progress(8, now - Duration::days(2)));
+    assert!(item.complete);
+    assert_eq!(item.progress, 10);
+
+    item.reset_if_due(now);
+    assert!(!item.complete);
+    assert_eq!(item.progress, 0);
+
+    let mut plain = TodoItem::new("Walk".to_string(), None);
+    assert!(!plain.add_progress(1, now));
+    assert_eq!(plain.progress, 0);
+}
+
+#[test]
+fn cli_lists_pinned_tasks_once_above_the_rest() {
+    let sandbox = Sandbox::new("pin");
+    let todo = |args: &[&str]| {
+        let output = sandbox.run(args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    todo(&["add", "Water plants"]);
+    todo(&["add", "Pay rent"]);
+    todo(&["pin", "1"]);
+
+    let listing = todo(&["list"]);
+    assert_eq!(listing.matches("Pay rent").count(), 1, "{}", listing);
+    let pinned = listing.find("=== Pinned ===").unwrap();
+    let rent = listing.find("📌 [1] Pay rent").unwrap();
+    let incomplete = listing.find("=== Incomplete tasks ===").unwrap();
+    assert!(pinned < rent && rent < incomplete, "{}", listing);
+
+    todo(&["unpin", "1"]);
+    assert!(!todo(&["list"]).contains("Pinned"));
+}
+
+#[test]
+fn due_time_delays_repeat_until_that_time_on_the_due_day() {
+    let at = |day, hour, min| Local.with_ymd_and_hms(2026, 3, day, hour, min, 0).unwrap();
+    let mut item = TodoItem::new("Take medication".to_string(), Some(1));
+    item.due_time = Some(parse_time_of_day("08:00").unwrap());
+    item.mark_complete(at(10, 20, 0));
+
+    assert!(!item.should_reset(at(11, 7, 59)));
+    assert!(item.should_reset(at(11, 8, 0)));
+
+    // Weekday repeats move from midnight to the due time too (2026-03-12 is a Thursday).
+    item.repeat_days = None;
+    item.repeat_spec = Some(RepeatSpec::Weekdays(vec![Weekday::Thu]));
+    assert!(!item.should_reset(at(12, 7, 59)));
+    assert!(item.should_reset(at(12, 8, 0)));
+
+    // Without a due time the interval still runs from the completion time.
+    let mut plain = TodoItem::new("Stretch".to_string(), Some(1));
+    plain.mark_complete(at(10, 20, 0));
+    assert!(!plain.should_reset(at(11, 19, 59)));
+    assert!(plain.should_reset(at(11, 20, 0)));
+
+    assert_eq!(exit_code(&parse_time_of_day("8am").unwrap_err()), 4);
+}
+
+#[test]
+fn diff_matches_tasks_by_text_and_reports_state_changes() {
+    let now = Local.with_ymd_and_hms(2026, 1, 14, 10, 0, 0).unwrap();
+    let task = |text: &str, repeat| TodoItem::new(text.to_string(), repeat);
+    let here = TodoList {
+        items: vec![task("Feed gecko", Some(2)), task("Read", None), task("Call mum", None)],
+        ..TodoList::default()
+    };
+    let mut there = TodoList {
+        items: vec![task("Read", None), task("Feed gecko ", Some(2)), task("Buy milk", None)],
+        ..TodoList::default()
+    };
+    assert_eq!(
+        here.diff(&there),
+        ListDiff { only_here: vec![2], only_there: vec![2], changed: vec![] }
+    );
+
+    there.items[0].mark_complete(now);
+    there.items[1].repeat_days = Some(3);
+    assert_eq!(here.diff(&there).changed, vec![(0, 1), (1, 0)]);
+    assert!(here.diff(&here).is_empty());
+}
+
+#[test]
+fn cli_diff_never_rewrites_the_other_file() {
+    let todo = Sandbox::new("diff");
+    let other = todo.dir.join("other.ron");
+    // Unversioned and plain, so `load` would migrate it and encrypt it on save.
+    let contents = r#"(items: [(text: "Buy milk", complete: false, complete_date: None, repeat_days: None)])"#;
+    std::fs::write(&other, contents).unwrap();
+
+    let diff = |key: Option<&str>| {
+        let mut command = todo.command();
+        command.arg("diff").arg(&other);
+        if let Some(key) = key {
+            command.env("TODOSTER_KEY", key);
+        }
+        String::from_utf8(command.output().unwrap().stdout).unwrap()
+    };
+
+    todo.run(&["add", "Feed gecko"]);
+    let output = diff(None);
+    assert!(output.contains("  [0] Buy milk"), "{}", output);
+    assert_eq!(std::fs::read_to_string(&other).unwrap(), contents);
+
+    assert!(diff(Some("s3cret")).contains("  [0] Buy milk"));
+    assert_eq!(std::fs::read_to_string(&other).unwrap(), contents);
+}
+
+#[test]
+fn duplicate_groups_keep_completed_then_oldest_copy() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    for (text, created_days_ago) in [("Buy milk", 1), ("Call mum", 5), ("buy milk ", 3), ("Buy milk", 9), ("Call mum", 2)] {
+        let mut item = TodoItem::new(text.to_string(), None);
+        item.created_at = Some(now - Duration::days(created_days_ago));
+        list.items.push(item);
+    }
+    list.items[4].mark_complete(now);
+
+    assert_eq!(list.duplicate_groups(false), vec![vec![3, 0], vec![4, 1]]);
+    assert_eq!(list.duplicate_groups(true), vec![vec![3, 0, 2], vec![4, 1]]);
+}
+
+#[test]
+fn legacy_data_file_is_moved_only_when_new_one_is_missing() {
+    let root = std::env::temp_dir().join(format!("todoster-migrate-{}", std::process::id()));
+    let old = root.join("config/todoster/todos.ron");
+    let new = root.join("data/todoster/todos.ron");
+    std::fs::create_dir_all(old.parent().unwrap()).unwrap();
+    std::fs::write(&old, "(items: [])").unwrap();
+
+    assert!(migrate_data_file(&old, &new).unwrap());
+    assert!(!old.exists());
+    assert_eq!(std::fs::read_to_string(&new).unwrap(), "(items: [])");
+
+    // Never overwrite an existing data file.
+    std::fs::write(&old, "(items: [stale])").unwrap();
+    assert!(!migrate_data_file(&old, &new).unwrap());
+    assert_eq!(std::fs::read_to_string(&new).unwrap(), "(items: [])");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn cli_edit_sets_priority_and_text_in_one_call() {
+    let sandbox = Sandbox::new("priority");
+    let todo = |args: &[&str]| assert!(sandbox.run(args).status.success());
+    let item = || sandbox.load().items.remove(0);
+
+    todo(&["add", "Renew passport"]);
+    todo(&["edit", "0", "--priority", "high", "--text", "Renew passport online"]);
+    let edited = item();
+    assert_eq!(edited.priority, Some(Priority::High));
+    assert_eq!(edited.text, "Renew passport online");
+
+    // Clearing wins over setting in the same call.
+    todo(&["edit", "0", "--priority", "low", "--clear-priority"]);
+    assert_eq!(item().priority, None);
+}
+
+#[test]
+fn list_stats_count_states_and_oldest_incomplete() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    let mut old = TodoItem::new("Sort photos".to_string(), None);
+    old.created_at = Some(now - Duration::days(30));
+    let mut newer = TodoItem::new("Feed gecko".to_string(), Some(2));
+    newer.created_at = Some(now - Duration::days(3));
+    let mut done = TodoItem::new("Water plants".to_string(), Some(7));
+    done.created_at = Some(now - Duration::days(90));
+    done.mark_complete(now);
+    list.items.extend([old, newer, done]);
+
+    let stats = list.stats(now, &PriorityWeights::default());
+    assert_eq!(
+        stats,
+        ListStats {
+            total: 3,
+            complete: 1,
+            incomplete: 2,
+            repeating: 2,
+            overdue: 1,
+            oldest_incomplete_days: Some(30),
+            weighted_progress: Some(33.3),
+        }
+    );
+    let empty = TodoList::default().stats(now, &PriorityWeights::default());
+    assert_eq!((empty.oldest_incomplete_days, empty.weighted_progress), (None, None));
+}
+
+#[test]
+fn cli_repeat_display_days_drops_hours() {
+    let sandbox = Sandbox::new("display");
+    let todo = |args: &[&str]| {
+        let output = sandbox.run(args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    todo(&["add", "Feed gecko", "--repeat", "2"]);
+    todo(&["complete", "0"]);
+
+    assert!(todo(&["list"]).contains("(repeat in 1d, 23hrs)"));
+    assert!(todo(&["--repeat-display", "days", "list"]).contains("(repeat in 2d)"));
+}
+
+#[test]
+fn cli_bulk_complete_over_threshold_needs_yes() {
+    let todo = Sandbox::new("bulk");
+    let completed = || todo.load().items.iter().filter(|item| item.complete).count();
+
+    for n in 0..12 {
+        todo.run(&["add", &format!("Task {}", n)]);
+    }
+
+    assert!(todo.run(&["complete", "0-9"]).status.success());
+    assert_eq!(completed(), 10);
+
+    let refused = todo.run(&["complete", "0-11"]);
+    assert_eq!(refused.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("About to affect 12 tasks"));
+    assert_eq!(completed(), 10);
+    // Quiet only trims output; it doesn't confirm.
+    assert_eq!(todo.run(&["--quiet", "complete", "0-11"]).status.code(), Some(4));
+    assert_eq!(completed(), 10);
+
+    assert!(todo.run(&["--yes", "complete", "0-11"]).status.success());
+    assert_eq!(completed(), 12);
+}
+
+#[test]
+fn first_and_last_follow_display_order() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    for text in ["Older done", "Plain", "Pinned", "Newer done"] {
+        list.items.push(TodoItem::new(text.to_string(), None));
+    }
+    list.items[0].mark_complete(now - Duration::days(2));
+    list.items[3].mark_complete(now - Duration::days(1));
+    list.items[2].pinned = true;
+    // Inbox tasks aren't listed, so they're never first or last.
+    let mut captured = TodoItem::new("Captured".to_string(), None);
+    captured.tags.push(INBOX_TAG.to_string());
+    list.items.push(captured);
+
+    assert_eq!(list.display_order(now, None), vec![2, 1, 3, 0]);
+    assert_eq!("first".parse::<TaskRef>().unwrap().resolve(&list, now, None).unwrap(), 2);
+    assert_eq!("LAST".parse::<TaskRef>().unwrap().resolve(&list, now, None).unwrap(), 0);
+    assert_eq!("7".parse::<TaskRef>(), Ok(TaskRef::Index(7)));
+    assert!("middle".parse::<TaskRef>().is_err());
+
+    let err = TaskRef::Last.resolve(&TodoList::default(), now, None).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+}
+
+#[test]
+fn cli_first_follows_the_configured_sort() {
+    let todo = Sandbox::new("first-sorted");
+    todo.write_config(r#"(sort: Some("priority"))"#);
+    todo.run(&["add", "alpha"]);
+    todo.run(&["add", "beta", "-p", "high"]);
+
+    assert!(todo.run(&["complete", "first"]).status.success());
+    let list = todo.load();
+    assert!(list.items[1].complete);
+    assert!(!list.items[0].complete);
+}
+
+#[test]
+fn cli_upgrades_unversioned_files_and_refuses_newer_ones() {
+    let todo = Sandbox::new("version");
+
+    std::fs::write(&todo.file, r#"(items: [(text: "Feed gecko", complete: false, complete_date: None, repeat_days: None)])"#).unwrap();
+    assert!(todo.run(&["list"]).status.success());
+    let upgraded = todo.load();
+    assert_eq!(upgraded.version, SCHEMA_VERSION);
+    assert_eq!(upgraded.items[0].text, "Feed gecko");
+
+    std::fs::write(&todo.file, format!("(version: {}, items: [], shiny_new_thing: 1)", SCHEMA_VERSION + 1)).unwrap();
+    let refused = todo.run(&["list"]);
+    assert_eq!(refused.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("please upgrade"));
+}
+
+#[test]
+fn toggle_flips_state_and_history() {
+    let now = Local::now();
+    let mut item = TodoItem::new("Feed gecko".to_string(), Some(2));
+
+    assert!(item.toggle(now));
+    assert_eq!(item.complete_date, Some(now));
+    assert_eq!(item.completion_history, vec![now]);
+
+    assert!(!item.toggle(now));
+    assert_eq!(item.complete_date, None);
+    assert!(item.completion_history.is_empty());
+}
+
+#[test]
+fn cli_list_wrap_indents_continuation_lines_under_the_text() {
+    let sandbox = Sandbox::new("wrap");
+    sandbox.store(&TodoList {
+        items: vec![TodoItem::new("Write the quarterly report covering churn".to_string(), None)],
+        ..TodoList::default()
+    });
+    let todo = |wrap: &str| {
+        let output = sandbox.run(&["list", "--wrap", wrap]);
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let wrapped = todo("25");
+    assert!(wrapped.contains("[0] Write the quarterly\n    report covering churn\n"), "{}", wrapped);
+    assert!(todo("0").contains("[0] Write the quarterly report covering churn\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn cli_edit_file_undoes_an_edit_that_does_not_load() {
+    let sandbox = Sandbox::new("edit-file");
+    sandbox.store(&TodoList {
+        items: vec![TodoItem::new("Feed gecko".to_string(), None)],
+        ..TodoList::default()
+    });
+    // `sed -i` makes a handy non-interactive editor.
+    let edit = |script: &str| {
+        sandbox
+            .command()
+            .arg("edit-file")
+            .env_remove("VISUAL")
+            .env("EDITOR", format!("sed -i {}", script))
+            .output()
+            .unwrap()
+    };
+    let texts = || sandbox.load().items.into_iter().map(|item| item.text).collect::<Vec<_>>();
+
+    assert!(edit("s/Feed/Walk/").status.success());
+    assert_eq!(texts(), ["Walk gecko"]);
+
+    let output = edit("s/items/itemz/");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Edit undone"));
+    assert_eq!(texts(), ["Walk gecko"]);
+    let mut rejected = sandbox.file.clone().into_os_string();
+    rejected.push(".rejected");
+    assert!(std::fs::read_to_string(&rejected).unwrap().contains("itemz"));
+}
+
+#[test]
+fn reminders_start_exactly_at_the_lead_time() {
+    let at = |h, m| Local.with_ymd_and_hms(2026, 1, 14, h, m, 0).unwrap();
+    assert_eq!(parse_lead_time("2h").unwrap(), 120);
+    assert_eq!(parse_lead_time("1w").unwrap(), 7 * 24 * 60);
+    for bad in ["0m", "2x", "h"] {
+        assert_eq!(exit_code(&parse_lead_time(bad).unwrap_err()), 4, "{}", bad);
+    }
+
+    let mut item = TodoItem::new("Submit timesheet".to_string(), None);
+    item.due_date = Some(at(12, 0));
+    assert_eq!(item.reminder_countdown(at(11, 0)), None);
+
+    item.reminder_lead_minutes = Some(120);
+    assert_eq!(item.reminder_countdown(at(9, 59)), None);
+    assert_eq!(item.reminder_countdown(at(10, 0)), Some(Duration::hours(2)));
+    assert_eq!(item.reminder_countdown(at(12, 0)), Some(Duration::zero()));
+    assert_eq!(item.reminder_countdown(at(12, 1)), None);
+
+    item.mark_complete(at(10, 30));
+    assert_eq!(item.reminder_countdown(at(11, 0)), None);
+}
+
+#[test]
+fn cli_remind_is_silent_unless_something_is_due() {
+    let todo = Sandbox::new("remind");
+    let remind = || {
+        let output = todo.run(&["remind"]);
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let now = Local::now();
+    let mut list = TodoList::default();
+    let mut fed = TodoItem::new("Feed gecko".to_string(), Some(2));
+    fed.mark_complete(now);
+    list.items.push(fed);
+    list.items.push(TodoItem::new("Sort photos".to_string(), None));
+
+    todo.store(&list);
+    assert_eq!(remind(), "");
+
+    // Completed three days ago: the repeat has come round again.
+    list.items[0].complete_date = Some(now - Duration::days(3));
+    todo.store(&list);
+    assert_eq!(remind(), "[0] Feed gecko\n");
+}
+
+#[test]
+fn cli_replay_last_reruns_the_last_change() {
+    let todo = Sandbox::new("replay");
+
+    assert_eq!(todo.run(&["replay-last"]).status.code(), Some(4));
+
+    assert!(todo.run(&["add", "Standup notes"]).status.success());
+    assert!(todo.run(&["list"]).status.success());
+    // A preview changes nothing, so it isn't what gets replayed.
+    assert!(todo.run(&["delete", "0"]).status.success());
+    let output = todo.run(&["replay-last"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("Replaying: todo --file"));
+
+    let texts: Vec<_> = todo.load().items.into_iter().map(|item| item.text).collect();
+    assert_eq!(texts, ["Standup notes", "Standup notes"]);
+}
+
+#[test]
+fn cli_merge_files_lists_all_sources_but_refuses_writes() {
+    let sandbox = Sandbox::new("merge-files");
+    for (name, text) in [("work", "Send report"), ("home", "Feed gecko")] {
+        let mut list = TodoList::default();
+        list.items.push(TodoItem::new(text.to_string(), None));
+        std::fs::write(sandbox.dir.join(format!("{}.ron", name)), ron::to_string(&list).unwrap()).unwrap();
+    }
+    let files = format!("{},{}", sandbox.dir.join("work.ron").display(), sandbox.dir.join("home.ron").display());
+    let todo = |args: &[&str]| sandbox.bare().args(["--merge-files", &files]).args(args).output().unwrap();
+
+    let listing = todo(&["list"]);
+    assert!(listing.status.success());
+    let stdout = String::from_utf8(listing.stdout).unwrap();
+    assert!(stdout.contains("[0] work: Send report"), "{}", stdout);
+    assert!(stdout.contains("[1] home: Feed gecko"), "{}", stdout);
+
+    assert_eq!(todo(&["add", "Anything"]).status.code(), Some(4));
+
+    // Viewing never rewrites a source, even an old-format one with a key set.
+    let old = r#"(items: [(text: "Water plants", complete: false, complete_date: None, repeat_days: None)])"#;
+    std::fs::write(sandbox.dir.join("home.ron"), old).unwrap();
+    let output = sandbox.bare().args(["--merge-files", &files, "list"]).env("TODOSTER_KEY", "s3cret").output().unwrap();
+    assert!(String::from_utf8(output.stdout).unwrap().contains("home: Water plants"));
+    assert_eq!(std::fs::read_to_string(sandbox.dir.join("home.ron")).unwrap(), old);
+}
+
+#[test]
+fn snapshot_log_keeps_newest_within_cap() {
+    let now = Local::now();
+    let mut log = SnapshotLog::default();
+    for n in 0..MAX_SNAPSHOTS + 5 {
+        let mut list = TodoList::default();
+        list.items.push(TodoItem::new(format!("Task {}", n), None));
+        log.push(Snapshot {
+            taken_at: now,
+            command: "add task".to_string(),
+            list,
+        })
+        .unwrap();
+    }
+
+    assert_eq!(log.snapshots.len(), MAX_SNAPSHOTS);
+    let newest = format!("Task {}", MAX_SNAPSHOTS + 4);
+    assert_eq!(log.nth_newest(1).unwrap().list.items[0].text, newest);
+    assert_eq!(log.nth_newest(MAX_SNAPSHOTS).unwrap().list.items[0].text, "Task 5");
+    assert!(log.nth_newest(0).is_none());
+    assert!(log.nth_newest(MAX_SNAPSHOTS + 1).is_none());
+}
+
+#[test]
+fn cli_add_after_and_before_insert_next_to_a_task() {
+    let todo = Sandbox::new("insert");
+    let texts = || todo.load().items.into_iter().map(|item| item.text).collect::<Vec<_>>();
+
+    todo.run(&["add", "A"]);
+    todo.run(&["add", "C"]);
+    assert_eq!(todo.stdout(&["add", "B", "--after", "0"]).trim(), "Task added at [1].");
+    todo.run(&["add", "Start", "--before", "0"]);
+    todo.run(&["add", "End", "--after", "last"]);
+    assert_eq!(texts(), ["Start", "A", "B", "C", "End"]);
+
+    assert_eq!(todo.run(&["add", "X", "--after", "9"]).status.code(), Some(4));
+    assert_eq!(todo.run(&["add", "X", "--after", "0", "--before", "1"]).status.code(), Some(4));
+    assert_eq!(texts().len(), 5);
+}
+
+#[test]
+fn weighted_progress_counts_high_priority_tasks_more() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    for priority in [Some(Priority::High), Some(Priority::Low), None] {
+        let mut item = TodoItem::new("Task".to_string(), None);
+        item.priority = priority;
+        list.items.push(item);
+    }
+    list.items[0].mark_complete(now);
+
+    // High (3) done out of 3 + 1 + 1.
+    assert_eq!(list.stats(now, &PriorityWeights::default()).weighted_progress, Some(60.0));
+
+    let flat = PriorityWeights { high: 1, medium: 1, low: 1, none: 1 };
+    assert_eq!(list.stats(now, &flat).weighted_progress, Some(33.3));
+}
+
+#[test]
+fn cli_group_by_tag_lists_multi_tagged_tasks_under_each_tag() {
+    let todo = Sandbox::new("group");
+
+    todo.run(&["add", "Report", "-t", "work", "-t", "urgent"]);
+    todo.run(&["add", "Nap"]);
+    todo.run(&["add", "Email", "-t", "work"]);
+    todo.run(&["complete", "0"]);
+
+    let listing = todo.stdout(&["list", "--group-by", "tag"]);
+    let sections: Vec<&str> = listing.split("\n\n").collect();
+    assert_eq!(sections.len(), 3, "{}", listing);
+    assert!(sections[0].starts_with("=== #urgent ===\n[0] Report"), "{}", listing);
+    // Incomplete before complete within a group.
+    assert!(sections[1].starts_with("=== #work ===\n[2] Email #work\n[0] Report"), "{}", listing);
+    assert_eq!(sections[2].trim_end(), "=== (untagged) ===\n[1] Nap");
+}
+
+#[test]
+fn cli_doctor_reports_problems_without_touching_the_file() {
+    let todo = Sandbox::new("doctor");
+    // Unversioned (so `load` would migrate it) and inconsistent.
+    let contents = r#"(items: [
+        (text: "Water plants", complete: true, complete_date: None, repeat_days: Some(0)),
+        (text: "Read", complete: false, complete_date: None, repeat_days: None),
+    ])"#;
+    std::fs::write(&todo.file, contents).unwrap();
+
+    let output = todo.run(&["doctor"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("[0] is complete but has no complete_date"), "{}", stdout);
+    assert!(stdout.contains("[0] repeats every 0 day(s)"), "{}", stdout);
+    assert!(!stdout.contains("[1]"), "{}", stdout);
+    assert_eq!(std::fs::read_to_string(&todo.file).unwrap(), contents);
+    assert!(!SnapshotLog::path_for(&todo.file).exists());
+}
+
+#[test]
+fn cli_doctor_fix_repairs_and_keeps_a_backup() {
+    let todo = Sandbox::new("doctor-fix");
+    let backup = PathBuf::from(format!("{}.bak", todo.file.display()));
+    let contents = r#"(version: 1, items: [
+        (text: "Water plants", complete: true, complete_date: None, repeat_days: Some(-2)),
+        (text: "Stretch", complete: false, complete_date: None, repeat_days: None, repeat_spec: Some(Hours(0))),
+        (text: "Read", complete: false, complete_date: None, repeat_days: None, id: 5),
+        (text: "Write", complete: false, complete_date: None, repeat_days: None, id: 5),
+    ])"#;
+    std::fs::write(&todo.file, contents).unwrap();
+
+    let output = todo.run(&["doctor"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("[3] has id #5, already used by an earlier task"));
+
+    let output = todo.run(&["doctor", "--fix"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("fixed: [0] complete_date set to now"), "{}", stdout);
+    assert!(stdout.contains("fixed: [0] repeat interval set to 1 day"), "{}", stdout);
+    assert!(stdout.contains("fixed: [1] repeat interval set to 1 hour"), "{}", stdout);
+    assert!(stdout.contains("fixed: [3] given new id #"), "{}", stdout);
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), contents);
+
+    let list = todo.load();
+    assert!(list.items[0].complete_date.is_some());
+    assert_eq!(list.items[0].repeat_days, Some(1));
+    assert_eq!(list.items[1].repeat_spec, Some(RepeatSpec::Hours(1)));
+    assert_eq!(list.items[2].id, 5);
+    assert_ne!(list.items[3].id, 5);
+
+    // Nothing left to do: no second save, so the backup stays the original.
+    std::fs::remove_file(&backup).unwrap();
+    let output = todo.run(&["doctor", "--fix"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("No problems found."));
+    assert!(!backup.exists());
+}
+
+#[test]
+fn date_format_comes_from_config_and_is_validated() {
+    let date = Local.with_ymd_and_hms(2026, 3, 4, 17, 5, 0).unwrap();
+    assert_eq!(format_date(date, DEFAULT_DATE_FORMAT), "2026-03-04 17:05");
+    assert_eq!(format_date(date, "%d.%m.%Y"), "04.03.2026");
+
+    let file = std::env::temp_dir().join(format!("todoster-date-format-{}.ron", std::process::id()));
+    std::fs::write(&file, r#"(date_format: Some("%d/%m %Q"))"#).unwrap();
+    let err = Config::load(&file).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+
+    std::fs::write(&file, r#"(date_format: Some("%d/%m"))"#).unwrap();
+    assert_eq!(Config::load(&file).unwrap().date_format.as_deref(), Some("%d/%m"));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn cli_repeat_end_uses_the_date_format() {
+    let todo = Sandbox::new("until-format");
+    todo.write_config(r#"(date_format: Some("%d.%m.%Y"))"#);
+    let mut item = TodoItem::new("Water plants".to_string(), Some(2));
+    item.repeat_until = Some(Local.with_ymd_and_hms(2099, 5, 1, 0, 0, 0).unwrap());
+    todo.store(&TodoList {
+        items: vec![item],
+        ..TodoList::default()
+    });
+
+    assert!(todo.stdout(&["list"]).contains("until 01.05.2099)"));
+    assert!(todo.stdout(&["--iso-dates", "list"]).contains("until 2099-05-01T00:00:00"));
+}
+
+#[test]
+fn default_sort_comes_from_config_and_ignores_unknown_keys() {
+    let file = std::env::temp_dir().join(format!("todoster-config-sort-{}.ron", std::process::id()));
+    std::fs::write(&file, r#"(sort: Some("Priority"), sort_reverse: true)"#).unwrap();
+    let config = Config::load(&file).unwrap();
+    assert_eq!(config.sort_key(), Some(SortKey::Priority));
+    assert!(config.sort_reverse);
+
+    std::fs::write(&file, r#"(sort: Some("due"))"#).unwrap();
+    assert_eq!(Config::load(&file).unwrap().sort_key(), Some(SortKey::Due));
+
+    std::fs::write(&file, r#"(sort: Some("urgency"))"#).unwrap();
+    let config = Config::load(&file).unwrap();
+    assert_eq!(config.sort, None);
+    assert_eq!(config.sort_key(), None);
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn cli_track_accumulates_time_against_the_estimate() {
+    let todo = Sandbox::new("track");
+
+    todo.run(&["add", "Write report", "--estimate", "45"]);
+    assert_eq!(todo.stdout(&["track", "0", "--spent", "30"]), "Logged 30 min on task 0 (30/45 min).\n");
+    todo.run(&["track", "0", "--spent", "20"]);
+
+    let list = todo.load();
+    assert_eq!(list.items[0].spent_minutes, 50);
+    assert!(!list.items[0].complete);
+    assert!(todo.stdout(&["list"]).contains("[0] Write report (50/45 min) (over estimate)"));
+}
+
+#[test]
+fn cli_ascii_output_has_no_unicode() {
+    let todo = Sandbox::new("ascii");
+
+    todo.run(&["add", "Café run"]);
+    todo.run(&["pin", "0"]);
+
+    let listing = todo.stdout(&["--ascii", "--no-color", "list"]);
+    assert!(listing.is_ascii(), "{}", listing);
+    assert!(listing.starts_with("-- Pinned --\n* [0] Caf? run\n"), "{}", listing);
+    assert!(todo.stdout(&["--plain", "stats"]).starts_with("-- Stats --\n"));
+    let commands = todo.stdout(&["--ascii", "commands"]);
+    assert!(commands.is_ascii() && commands.contains("\n-- Exit codes --\n"), "{}", commands);
+    assert!(todo.stdout(&["list"]).contains("📌 [0] Café run"));
+}
+
+#[test]
+fn cli_undo_accepts_ranges() {
+    let todo = Sandbox::new("undo");
+
+    for text in ["A", "B", "C", "D"] {
+        todo.run(&["add", text]);
+    }
+    todo.run(&["complete", "0-3"]);
+
+    let output = todo.run(&["undo", "1-3,9"]);
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    assert_eq!(stdout, "Task 1 marked incomplete.\nTask 2 marked incomplete.\nTask 3 marked incomplete.\n");
+    assert!(stderr.contains("Index 9 does not exist"), "{}", stderr);
+
+    let complete: Vec<bool> = todo.load().items.iter().map(|item| item.complete).collect();
+    assert_eq!(complete, [true, false, false, false]);
+}
+
+#[test]
+fn cli_version_reports_build_and_resolved_path() {
+    let todo = Sandbox::new("version-info");
+    let output = todo.run(&["version"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("todoster {}\n", env!("CARGO_PKG_VERSION"))), "{}", stdout);
+    assert!(stdout.contains("rustc:   rustc "), "{}", stdout);
+    assert!(stdout.contains(&format!("data:    {}\n", todo.file.display())), "{}", stdout);
+    assert!(!todo.file.exists());
+}
+
+#[test]
+fn sort_by_priority_breaks_ties_by_created_at() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    let tasks = [
+        ("Low", Some(Priority::Low), 5),
+        ("None", None, 9),
+        ("High, newer", Some(Priority::High), 1),
+        ("High, older", Some(Priority::High), 3),
+        ("Medium", Some(Priority::Medium), 2),
+    ];
+    for (text, priority, days_ago) in tasks {
+        let mut item = TodoItem::new(text.to_string(), None);
+        item.priority = priority;
+        item.created_at = Some(now - Duration::days(days_ago));
+        list.items.push(item);
+    }
+
+    let mut order: Vec<usize> = (0..list.items.len()).collect();
+    list.sort_indexes(&mut order, SortKey::Priority, false, None);
+    assert_eq!(order, [3, 2, 4, 0, 1]);
+
+    list.sort_indexes(&mut order, SortKey::Priority, true, None);
+    assert_eq!(order, [1, 0, 4, 2, 3]);
+}
+
+#[test]
+fn sort_by_due_puts_undated_tasks_last() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    let tasks = [
+        ("Undated", None, None, 5),
+        ("Later", Some(3), None, 4),
+        ("Sooner", Some(1), None, 3),
+        ("Daily", None, Some(1), 2),
+    ];
+    for (text, due_in, repeat, days_ago) in tasks {
+        let mut item = TodoItem::new(text.to_string(), None);
+        item.due_date = due_in.map(|days| now + Duration::days(days));
+        item.repeat_days = repeat;
+        item.created_at = Some(now - Duration::days(days_ago));
+        list.items.push(item);
+    }
+
+    let mut order: Vec<usize> = (0..list.items.len()).collect();
+    list.sort_indexes(&mut order, SortKey::Due, false, None);
+    assert_eq!(order, [2, 1, 0, 3]);
+
+    list.sort_indexes(&mut order, SortKey::Due, true, None);
+    assert_eq!(order, [3, 0, 1, 2]);
+
+    // A waiting repeat is due now, ahead of every later deadline.
+    list.sort_indexes(&mut order, SortKey::Due, false, Some(now));
+    assert_eq!(order, [3, 2, 1, 0]);
+}
+
+#[test]
+fn cli_random_only_picks_matching_incomplete_tasks() {
+    let todo = Sandbox::new("random");
+
+    todo.run(&["add", "Pay rent", "-t", "finance", "-p", "high"]);
+    todo.run(&["add", "File taxes", "-t", "finance"]);
+    todo.run(&["add", "Nap"]);
+    todo.run(&["complete", "1"]);
+    todo.run(&["add", "Cancel gym", "-t", "finance", "--inbox"]);
+
+    for _ in 0..5 {
+        let picked = todo.stdout(&["random", "--tag", "#finance"]);
+        assert!(picked.starts_with("[0] Pay rent\n  tags:     #finance\n  priority: high\n"), "{}", picked);
+    }
+    assert_eq!(todo.stdout(&["random", "--tag", "garden"]), "Nothing to pick from.\n");
+}
+
+#[test]
+fn quick_add_extracts_priority_tags_and_due_date() {
+    let now = Local.with_ymd_and_hms(2026, 1, 20, 9, 0, 0).unwrap();
+
+    let quick = parse_quick_add("pay rent !high #finance @1st due:2026-02-01", now).unwrap();
+    assert_eq!(quick.text, "pay rent @1st");
+    assert_eq!(quick.priority, Some(Priority::High));
+    assert_eq!(quick.tags, ["finance"]);
+    assert_eq!(quick.due, Some(Local.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap()));
+
+    // Unknown priorities and bare markers are ordinary words.
+    let quick = parse_quick_add("shout !loudly # twice", now).unwrap();
+    assert_eq!(quick.text, "shout !loudly # twice");
+    assert_eq!(quick, QuickAdd { text: quick.text.clone(), ..QuickAdd::default() });
+
+    assert!(parse_quick_add("call due:someday", now).is_err());
+}
+
+#[test]
+fn list_accessors_wrap_the_items() {
+    let mut list = TodoList::default();
+    assert!(list.is_empty());
+    list.items.push(TodoItem::new("Feed gecko".to_string(), None));
+    list.items.push(TodoItem::new("Clean tank".to_string(), Some(7)));
+
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[1].repeat_days, Some(7));
+    assert!(list.get(2).is_none());
+
+    list.get_mut(0).unwrap().pinned = true;
+    list[1].text.push('!');
+    let texts: Vec<&str> = list.iter().map(|item| item.text.as_str()).collect();
+    assert_eq!(texts, ["Feed gecko", "Clean tank!"]);
+    assert_eq!((&list).into_iter().filter(|item| item.pinned).count(), 1);
+}
+
+#[test]
+fn encrypted_data_round_trips_and_rejects_a_wrong_passphrase() {
+    let key = Passphrase::new("correct horse");
+    let sealed = encrypt_data(b"(items: [])", &key).unwrap();
+    assert!(is_encrypted(&sealed));
+    assert_eq!(decrypt_data(&sealed, &key).unwrap(), b"(items: [])");
+
+    let err = decrypt_data(&sealed, &Passphrase::new("battery staple")).unwrap_err();
+    assert_eq!(exit_code(&err), 3);
+    assert!(err.to_string().contains("decryption failed"), "{}", err);
+
+    // Fresh salt and nonce every time.
+    assert_ne!(encrypt_data(b"(items: [])", &key).unwrap(), sealed);
+}
+
+#[test]
+fn cli_with_todoster_key_keeps_the_file_encrypted() {
+    let sandbox = Sandbox::new("encrypted");
+    let todo = |key: Option<&str>, args: &[&str]| {
+        let mut command = sandbox.command();
+        command.args(args);
+        if let Some(key) = key {
+            command.env("TODOSTER_KEY", key);
+        }
+        command.output().unwrap()
+    };
+
+    todo(Some("s3cret"), &["add", "Renew passport"]);
+    let stored = std::fs::read(&sandbox.file).unwrap();
+    assert!(is_encrypted(&stored));
+    assert!(!String::from_utf8_lossy(&stored).contains("passport"));
+    // The snapshot log holds the old list, so it is encrypted too.
+    todo(Some("s3cret"), &["add", "Book flights"]);
+    assert!(is_encrypted(&std::fs::read(SnapshotLog::path_for(&sandbox.file)).unwrap()));
+
+    let output = todo(Some("s3cret"), &["list"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("[0] Renew passport"));
+
+    let output = todo(Some("wrong"), &["list"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("decryption failed"));
+
+    let output = todo(None, &["list"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("is encrypted; set TODOSTER_KEY"));
+}
+
+#[test]
+fn cli_complete_dry_run_previews_the_schedule_without_saving() {
+    let todo = Sandbox::new("preview");
+
+    todo.run(&["add", "Water plants", "--repeat", "3", "--time", "08:00"]);
+    todo.run(&["add", "Post letter"]);
+    let before = std::fs::read_to_string(&todo.file).unwrap();
+
+    let preview = todo.stdout(&["complete", "0,1", "--dry-run"]);
+    let next_due = (Local::now().date_naive() + Duration::days(3)).format("%Y-%m-%d 08:00");
+    assert_eq!(
+        preview,
+        format!("Task 0: next due {}\nTask 1: (no repeat)\nDry run: nothing completed.\n", next_due)
+    );
+    assert_eq!(std::fs::read_to_string(&todo.file).unwrap(), before);
+}
+
+#[test]
+fn cli_templates_save_list_and_instantiate() {
+    let todo = Sandbox::new("templates");
+    let run = |args: &[&str]| {
+        let output = todo.run(args);
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+
+    let (_, saved) = run(&["template-save", "report", "Weekly report", "-r", "7", "-t", "work", "-p", "high"]);
+    assert_eq!(saved, "Template \"report\" saved.\n");
+    assert!(todo.dir.join("config/todoster/templates.ron").exists());
+    assert!(!todo.file.exists());
+
+    let (_, listing) = run(&["templates"]);
+    assert!(listing.starts_with("report           Weekly report #work (priority: high) (Repeat: 7 d)"), "{}", listing);
+
+    run(&["add-from-template", "report"]);
+    run(&["add-from-template", "report"]);
+    let list = todo.load();
+    assert_eq!(list.items.len(), 2);
+    assert_eq!(list.items[1].repeat_days, Some(7));
+    assert_eq!(list.items[1].tags, ["work"]);
+    assert_eq!(list.items[1].priority, Some(Priority::High));
+
+    // Presets and hourly repeats carry over like on `add`.
+    run(&["template-save", "standup", "Standup notes", "--repeat", "weekday"]);
+    run(&["template-save", "stretch", "Stretch", "--repeat", "4h"]);
+    assert_eq!(run(&["template-save", "nap", "Nap", "--repeat", "0h"]).0, Some(4));
+    run(&["add-from-template", "standup"]);
+    run(&["add-from-template", "stretch"]);
+    let list = todo.load();
+    assert_eq!(list.items[2].repeat_preset, Some(RepeatPreset::Weekday));
+    assert_eq!(list.items[2].repeat_spec, RepeatPreset::Weekday.schedule().1);
+    assert_eq!(list.items[3].repeat_spec, Some(RepeatSpec::Hours(4)));
+    assert!(run(&["templates"]).1.contains("Standup notes (Repeat: weekday)"));
+
+    assert_eq!(run(&["add-from-template", "missing"]).0, Some(4));
+}
+
+#[test]
+fn cli_list_new_shows_tasks_added_since_the_last_list() {
+    let todo = Sandbox::new("new");
+
+    todo.run(&["add", "Feed gecko"]);
+    // Nothing stored yet: everything counts as new.
+    let first = todo.stdout(&["list", "--new"]);
+    assert!(first.starts_with("(first look: everything is new)"), "{}", first);
+    assert!(first.contains("[0] Feed gecko"), "{}", first);
+
+    todo.run(&["add", "Clean tank"]);
+    let digest = todo.stdout(&["list", "--new"]);
+    assert!(digest.starts_with("(tasks added since "), "{}", digest);
+    assert!(digest.contains("[1] Clean tank"), "{}", digest);
+    assert!(!digest.contains("Feed gecko"), "{}", digest);
+
+    // That look counts too.
+    assert!(!todo.stdout(&["list", "--new"]).contains("Clean tank"));
+}
+
+#[test]
+fn negative_indexes_count_back_from_the_end() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    for text in ["Done", "Plain", "Pinned"] {
+        list.items.push(TodoItem::new(text.to_string(), None));
+    }
+    list.items[0].mark_complete(now);
+    list.items[2].pinned = true;
+
+    assert_eq!("-1".parse::<TaskRef>(), Ok(TaskRef::FromEnd(1)));
+    assert_eq!("-1".parse::<TaskRef>().unwrap().resolve(&list, now, None).unwrap(), 0);
+    assert_eq!("-3".parse::<TaskRef>().unwrap().resolve(&list, now, None).unwrap(), 2);
+    assert!("-0".parse::<TaskRef>().is_err());
+
+    let err = TaskRef::FromEnd(4).resolve(&list, now, None).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+    assert!(err.to_string().contains("-4 is out of range"), "{}", err);
+}
+
+#[test]
+fn cli_negative_indexes_in_index_lists() {
+    let todo = Sandbox::new("negative");
+
+    for text in ["A", "B", "C", "D"] {
+        todo.run(&["add", text]);
+    }
+    assert!(todo.run(&["complete", "-1"]).status.success());
+    assert!(todo.run(&["toggle", "--", "-2,0"]).status.success());
+    assert_eq!(todo.run(&["complete", "-9"]).status.code(), Some(4));
+
+    let complete: Vec<bool> = todo.load().items.iter().map(|item| item.complete).collect();
+    assert_eq!(complete, [true, false, true, true]);
+}
+
+#[test]
+fn cli_focus_limits_list_to_one_task_until_unfocus() {
+    let todo = Sandbox::new("focus");
+
+    for text in ["Email", "Write report", "Tidy desk"] {
+        todo.run(&["add", text]);
+    }
+    todo.run(&["focus", "1"]);
+
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.contains("[1] Write report"), "{}", listed);
+    assert!(!listed.contains("Email") && !listed.contains("Tidy desk"), "{}", listed);
+    assert!(listed.contains("todo unfocus"), "{}", listed);
+
+    assert_eq!(todo.load().focused(), Some(1));
+
+    assert_eq!(todo.stdout(&["unfocus"]), "Focus cleared.\n");
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.contains("Email") && listed.contains("Tidy desk"), "{}", listed);
+}
+
+#[test]
+fn odd_complete_dates_are_read_leniently() {
+    let item = |date: &str| {
+        format!(
+            "(text: \"T\", complete: true, complete_date: {}, repeat_days: None)",
+            date
+        )
+    };
+    let file = format!(
+        "(version: 1, items: [{}, {}, {}, {}, {}])",
+        item("Some(\"2026-03-01T09:30:00+00:00\")"),
+        item("Some(\"2026-03-01 09:30\")"),
+        item("Some(\"2026-03-01\")"),
+        item("Some(\"last tuesday\")"),
+        item("Some(42)"),
+    );
+
+    let list: TodoList = ron::from_str(&file).unwrap();
+    let dates: Vec<_> = list.items.iter().map(|item| item.complete_date).collect();
+    let utc = chrono::DateTime::parse_from_rfc3339("2026-03-01T09:30:00+00:00").unwrap();
+    assert_eq!(dates[0], Some(utc.with_timezone(&Local)));
+    assert_eq!(dates[1], Local.with_ymd_and_hms(2026, 3, 1, 9, 30, 0).earliest());
+    assert_eq!(dates[2], Local.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).earliest());
+    assert_eq!(dates[3], None);
+    assert_eq!(dates[4], None);
+}
+
+#[test]
+fn replace_text_matches_case_only_when_asked() {
+    assert_eq!(replace_text("Falcon: plan falcon", "falcon", "Osprey", false), Some("Falcon: plan Osprey".into()));
+    assert_eq!(replace_text("Falcon: plan falcon", "FALCON", "Osprey", true), Some("Osprey: plan Osprey".into()));
+    assert_eq!(replace_text("Straße bauen", "STRASSE", "Weg", true), None);
+    assert_eq!(replace_text("Ärger über Ärger", "ärger", "Ruhe", true), Some("Ruhe über Ruhe".into()));
+    assert_eq!(replace_text("Buy milk", "bread", "eggs", true), None);
+    assert_eq!(replace_text("Buy milk", "", "eggs", false), None);
+}
+
+#[test]
+fn cli_replace_dry_runs_until_confirmed() {
+    let todo = Sandbox::new("replace");
+    let texts = || todo.load().items.into_iter().map(|item| item.text).collect::<Vec<_>>();
+
+    for text in ["Falcon kickoff", "Email falcon team", "Buy milk"] {
+        todo.run(&["add", text]);
+    }
+
+    let preview = todo.stdout(&["replace", "Falcon", "Osprey"]);
+    assert!(preview.contains("1 task(s) would change"), "{}", preview);
+    assert!(preview.contains("  -> Osprey kickoff"), "{}", preview);
+    assert_eq!(texts(), ["Falcon kickoff", "Email falcon team", "Buy milk"]);
+
+    assert_eq!(
+        todo.stdout(&["replace", "falcon", "Osprey", "--ignore-case", "--confirm"]),
+        "Replaced text in 2 task(s).\n"
+    );
+    assert_eq!(texts(), ["Osprey kickoff", "Email Osprey team", "Buy milk"]);
+}
+
+#[test]
+fn cli_complete_and_delete_accept_space_separated_indexes() {
+    let todo = Sandbox::new("spaces");
+
+    for text in ["A", "B", "C", "D", "E", "F"] {
+        todo.run(&["add", text]);
+    }
+    assert!(todo.run(&["complete", "0", "2-3,5"]).status.success());
+    let complete: Vec<bool> = todo.load().items.iter().map(|item| item.complete).collect();
+    assert_eq!(complete, [true, false, true, true, false, true]);
+
+    assert!(todo.run(&["delete", "1", "4", "--confirm"]).status.success());
+    let texts: Vec<String> = todo.load().items.into_iter().map(|item| item.text).collect();
+    assert_eq!(texts, ["A", "C", "D", "F"]);
+}
+
+#[test]
+fn due_windows_classify_deadlines_at_the_boundaries() {
+    let now = Local.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap();
+    let windows = DueWindows { soon_days: 2, grace_days: 3 };
+    let due_in = |days: i64| {
+        let mut item = TodoItem::new("Report".to_string(), None);
+        item.due_date = Some(now + Duration::days(days));
+        windows.status(&item, now).unwrap()
+    };
+
+    assert_eq!(due_in(3), DueStatus::Later(3));
+    assert_eq!(due_in(2), DueStatus::Soon(2));
+    assert_eq!(due_in(1), DueStatus::Soon(1));
+    assert_eq!(due_in(0), DueStatus::Today);
+    assert_eq!(due_in(-3), DueStatus::Overdue(3));
+    assert_eq!(due_in(-4), DueStatus::LongOverdue(4));
+    assert!(!DueStatus::Later(3).is_pressing() && DueStatus::Soon(2).is_pressing());
+
+    let none = DueWindows { soon_days: 0, grace_days: 0 };
+    let mut item = TodoItem::new("Report".to_string(), None);
+    item.due_date = Some(now + Duration::days(1));
+    assert_eq!(none.status(&item, now), Some(DueStatus::Later(1)));
+    item.due_date = Some(now - Duration::days(1));
+    assert_eq!(none.status(&item, now), Some(DueStatus::LongOverdue(1)));
+    assert_eq!(DueWindows::default(), DueWindows { soon_days: 1, grace_days: 3 });
+}
+
+#[test]
+fn pressing_deadlines_are_listed_first() {
+    let now = Local::now();
+    let mut list = TodoList::default();
+    for (text, due) in [("No deadline", None), ("Next month", Some(30)), ("Tomorrow", Some(1)), ("Late", Some(-2))] {
+        let mut item = TodoItem::new(text.to_string(), None);
+        item.due_date = due.map(|days| now + Duration::days(days));
+        list.items.push(item);
+    }
+
+    assert_eq!(list.display_order(now, None), vec![2, 3, 0, 1]);
+    list.due_windows.soon_days = 0;
+    assert_eq!(list.display_order(now, None), vec![3, 0, 1, 2]);
+}
+
+#[test]
+fn cli_export_to_file_infers_format_and_refuses_to_overwrite() {
+    let todo = Sandbox::new("export");
+
+    todo.run(&["add", "Water plants"]);
+    let target = todo.dir.join("out/nested/todos.md");
+    let target_arg = target.to_str().unwrap();
+    assert!(todo.run(&["export", "--to", target_arg]).status.success());
+    assert!(std::fs::read_to_string(&target).unwrap().contains("- [ ] Water plants\n"));
+
+    assert_eq!(todo.run(&["export", "--to", target_arg]).status.code(), Some(4));
+    assert!(todo.run(&["export", "--to", target_arg, "--format", "json", "--force"]).status.success());
+    assert!(std::fs::read_to_string(&target).unwrap().contains("\"Water plants\""));
+
+    let unknown = todo.dir.join("todos.csv");
+    assert_eq!(todo.run(&["export", "--to", unknown.to_str().unwrap()]).status.code(), Some(4));
+    assert!(!unknown.exists());
+}
+
+#[test]
+fn cli_bump_adjusts_the_repeat_interval() {
+    let todo = Sandbox::new("bump");
+
+    todo.run(&["add", "Water ferns", "--repeat", "7"]);
+    todo.run(&["add", "Read"]);
+    assert_eq!(todo.stdout(&["bump", "0", "2"]), "Task 0 now repeats every 9 day(s) (was 7).\n");
+    assert_eq!(todo.stdout(&["bump", "0", "-20"]), "Task 0 now repeats every 1 day(s) (was 9).\n");
+    assert_eq!(todo.run(&["bump", "1", "1"]).status.code(), Some(4));
+
+    assert_eq!(todo.load().items[0].repeat_days, Some(1));
+}
+
+#[test]
+fn cli_list_banner_names_due_repeats() {
+    let todo = Sandbox::new("banner");
+
+    todo.run(&["add", "Read"]);
+    assert!(todo.stdout(&["list"]).starts_with("=== Incomplete tasks ==="));
+
+    todo.run(&["add", "Feed gecko", "--repeat", "2"]);
+    todo.run(&["add", "Water plants", "--repeat", "3"]);
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.starts_with("⚠ 2 repeating tasks are due: Feed gecko, Water plants\n\n"), "{}", listed);
+    assert!(todo.stdout(&["--ascii", "list"]).starts_with("! 2 repeating tasks are due"));
+    assert!(!todo.stdout(&["list", "--json"]).contains("repeating tasks are due"));
+
+    todo.run(&["complete", "1,2"]);
+    assert!(!todo.stdout(&["list"]).contains("are due"));
+}
+
+#[test]
+fn cli_warns_when_no_home_directory_is_known() {
+    let todo = Sandbox::new("nohome");
+
+    let output = todo.bare().args(["add", "Stray task"]).current_dir(&todo.dir).env_clear().output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert_eq!(stderr.matches("Warning: neither HOME nor").count(), 1, "{}", stderr);
+    assert!(stderr.contains("current directory"), "{}", stderr);
+    assert!(todo.dir.join("todoster/todos.ron").exists());
+}
+
+#[test]
+fn cli_list_columns_pick_and_order_fields() {
+    let todo = Sandbox::new("columns");
+
+    todo.run(&["add", "Pay rent", "--priority", "high", "--tag", "home"]);
+    assert!(todo.stdout(&["list"]).contains("[0] Pay rent #home (priority: high)\n"));
+    assert!(todo.stdout(&["list", "--columns", "priority,text"]).contains("\n(priority: high) Pay rent\n"));
+    assert!(todo.stdout(&["list", "--columns", "index,tags"]).contains("\n[0] #home\n"));
+
+    let bad = todo.run(&["list", "--columns", "text,colour"]);
+    assert_eq!(bad.status.code(), Some(4));
+    assert!(String::from_utf8(bad.stderr).unwrap().contains("index, text"));
+}
+
+#[test]
+fn auto_reset_counts_missed_occurrences() {
+    let midnight = |y, m, d| Local.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+    let now = midnight(2026, 3, 10) + Duration::hours(9);
+    let mut list = TodoList::default();
+
+    let mut daily = TodoItem::new("Stretch".to_string(), Some(1));
+    daily.mark_complete(now - Duration::days(7));
+    list.items.push(daily);
+
+    let mut weekly = TodoItem::new("Review".to_string(), None);
+    weekly.repeat_spec = Some(RepeatSpec::Weekdays(vec![Weekday::Mon]));
+    weekly.mark_complete(midnight(2026, 2, 20));
+    list.items.push(weekly);
+
+    let mut fresh = TodoItem::new("Water".to_string(), Some(2));
+    fresh.mark_complete(now - Duration::days(2));
+    list.items.push(fresh);
+
+    // Daily: due on the 4th, then the 5th through the 10th were missed too.
+    assert_eq!(list.items[0].missed_occurrences(now), 6);
+    let mut no_catchup = list.clone();
+    assert_eq!(list.auto_reset_repeating(now, true), 3);
+    let missed: Vec<u32> = list.items.iter().map(|item| item.missed_count).collect();
+    // Weekly (Fri 20th): due Mon 23rd, then Mon 2nd and Mon 9th.
+    assert_eq!(missed, [6, 2, 0]);
+
+    no_catchup.auto_reset_repeating(now, false);
+    assert!(no_catchup.items.iter().all(|item| item.missed_count == 0 && !item.complete));
+}
+
+#[test]
+fn streaks_grow_with_on_time_completions_and_reset_after_a_miss() {
+    let start = Local.with_ymd_and_hms(2026, 3, 1, 8, 0, 0).unwrap();
+    let mut item = TodoItem::new("Stretch".to_string(), Some(1));
+    assert_eq!(item.current_streak(start), 0);
+
+    // Three days running, each a little later than the last.
+    for (day, hour) in [(0, 0), (1, 2), (2, 5)] {
+        item.mark_complete(start + Duration::days(day) + Duration::hours(hour));
+    }
+    let after = start + Duration::days(2) + Duration::hours(6);
+    assert_eq!(item.current_streak(after), 3);
+    assert_eq!(item.best_streak, 3);
+
+    // Due again on the 4th; still alive that day, gone once the 5th's comes round.
+    assert_eq!(item.current_streak(after + Duration::days(1)), 3);
+    assert_eq!(item.current_streak(after + Duration::days(2)), 0);
+
+    // Coming back after the miss starts a new streak; the best is kept.
+    let back = start + Duration::days(6);
+    item.mark_complete(back);
+    assert_eq!(item.current_streak(back), 1);
+    item.mark_complete(back + Duration::days(1));
+    assert_eq!(item.current_streak(back + Duration::days(1)), 2);
+    assert_eq!(item.best_streak, 3);
+
+    let once = TodoItem::new("Once".to_string(), None);
+    assert_eq!(once.current_streak(back), 0);
+}
+
+#[test]
+fn cli_iso_dates_replace_countdowns_with_timestamps() {
+    let todo = Sandbox::new("iso");
+
+    todo.run(&["add", "Renew passport", "--due", "2099-06-01"]);
+    todo.run(&["add", "Walk", "--repeat", "2"]);
+    todo.run(&["complete", "1"]);
+
+    let relative = todo.stdout(&["list"]);
+    assert!(relative.contains("(due in "), "{}", relative);
+    assert!(relative.contains("(repeat in "), "{}", relative);
+
+    let next_due = todo.load().items[1].next_due_start().unwrap();
+    let iso = todo.stdout(&["--iso-dates", "list"]);
+    let due = Local.with_ymd_and_hms(2099, 6, 1, 0, 0, 0).unwrap();
+    assert!(iso.contains(&format!("(due {})", due.format(ISO_DATE_FORMAT))), "{}", iso);
+    assert!(iso.contains(&format!("(repeat due {})", next_due.format(ISO_DATE_FORMAT))), "{}", iso);
+    assert!(chrono::DateTime::parse_from_rfc3339(&next_due.format(ISO_DATE_FORMAT).to_string()).is_ok());
+}
+
+#[test]
+fn task_text_is_checked_against_the_length_limit() {
+    let text = "Read chapter four of the book".to_string();
+    assert_eq!(validate_task_text(text.clone(), None, false).unwrap(), (text.clone(), false));
+    assert_eq!(validate_task_text(text.clone(), Some(29), false).unwrap(), (text.clone(), false));
+
+    let err = validate_task_text(text.clone(), Some(12), false).unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+    assert!(err.to_string().contains("29 characters; the limit is 12"), "{}", err);
+
+    let (cut, truncated) = validate_task_text(text, Some(12), true).unwrap();
+    assert_eq!((cut.as_str(), truncated), ("Read chapte…", true));
+    assert_eq!(validate_task_text("Grüße an alle".into(), Some(6), true).unwrap().0, "Grüße…");
+
+    assert_eq!(exit_code(&validate_task_text("  ".into(), None, false).unwrap_err()), 4);
+}
+
+#[test]
+fn cli_complete_overdue_acknowledges_due_repeats() {
+    let todo = Sandbox::new("complete-overdue");
+
+    assert_eq!(todo.stdout(&["complete", "--overdue"]), "No repeating tasks are due.\n");
+    todo.run(&["add", "Feed gecko", "--repeat", "2"]);
+    todo.run(&["add", "Read"]);
+    todo.run(&["add", "Water plants", "--repeat", "3"]);
+
+    let preview = todo.stdout(&["complete", "--overdue", "--dry-run"]);
+    assert!(preview.starts_with("Task 0: next due ") && preview.contains("\nTask 2: next due "), "{}", preview);
+    assert!(preview.ends_with("Dry run: nothing completed.\n"), "{}", preview);
+
+    let done = todo.stdout(&["complete", "--overdue"]);
+    assert!(done.starts_with("Task 0 marked complete; next due "), "{}", done);
+    assert_eq!(done.lines().count(), 2, "{}", done);
+
+    let complete: Vec<bool> = todo.load().items.iter().map(|item| item.complete).collect();
+    assert_eq!(complete, [true, false, true]);
+}
+
+#[test]
+fn unknown_commands_get_a_suggestion() {
+    assert_eq!(edit_distance("complte", "complete"), 1);
+    assert_eq!(edit_distance("", "add"), 3);
+    let names = ["add", "complete", "completions", "stats", "stats-task"];
+    assert_eq!(suggest_command("complte", &names), Some("complete"));
+    assert_eq!(suggest_command("comp", &names), Some("complete"));
+    assert_eq!(suggest_command("STAT", &names), Some("stats"));
+    assert_eq!(suggest_command("zzz", &names), None);
+
+    let output = Sandbox::new("suggest").run(&["complte", "3"]);
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("'complete'"), "{}", stderr);
+    assert!(!stderr.contains("'templates'"), "{}", stderr);
+}
+
+#[test]
+fn cli_task_links_are_validated_and_shown() {
+    assert_eq!(validate_url(" https://example.com/a ").unwrap(), "https://example.com/a");
+    assert!(validate_url("HTTP://example.com").is_ok());
+    for bad in ["example.com", "ftp://example.com", "https://", "https://a b"] {
+        assert_eq!(exit_code(&validate_url(bad).unwrap_err()), 4, "{}", bad);
+    }
+
+    let todo = Sandbox::new("links");
+
+    assert_eq!(todo.run(&["add", "Read docs", "--url", "docs.rs"]).status.code(), Some(4));
+    todo.run(&["add", "Read docs", "--url", "https://docs.rs"]);
+    todo.run(&["add", "Stretch"]);
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.contains("Read docs 🔗"), "{}", listed);
+    assert!(!listed.contains("Stretch 🔗"), "{}", listed);
+
+    let output = todo.run(&["open", "1"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("has no link"));
+
+    todo.run(&["edit", "0", "--clear-url"]);
+    assert_eq!(todo.load().items[0].url, None);
+}
+
+#[cfg(not(feature = "clipboard"))]
+#[test]
+fn cli_clipboard_options_need_the_feature() {
+    let todo = Sandbox::new("clipboard");
+
+    assert_eq!(todo.run(&["add"]).status.code(), Some(4));
+    let output = todo.run(&["add", "--from-clipboard"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--features clipboard"));
+    assert!(!todo.file.exists());
+
+    let output = todo.run(&["export", "--to-clipboard"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    assert_eq!(todo.run(&["export", "--to-clipboard", "--to", "x.md"]).status.code(), Some(4));
+}
+
+#[test]
+fn cli_list_flat_keeps_storage_order_with_status_markers() {
+    let todo = Sandbox::new("flat");
+
+    for text in ["Email", "Stretch", "Read"] {
+        todo.run(&["add", text]);
+    }
+    todo.run(&["complete", "1"]);
+
+    let listed = todo.stdout(&["list", "--flat"]);
+    let lines: Vec<&str> = listed.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", listed);
+    assert_eq!(lines[0], "[ ] [0] Email");
+    assert!(lines[1].starts_with("[x] [1] Stretch"), "{}", listed);
+    assert_eq!(lines[2], "[ ] [2] Read");
+    assert!(!listed.contains("==="), "{}", listed);
+}
+
+#[test]
+fn cli_compact_saves_on_one_line_and_still_loads() {
+    let todo = Sandbox::new("compact");
+
+    todo.run(&["add", "Email"]);
+    let pretty = std::fs::read_to_string(&todo.file).unwrap();
+    assert!(pretty.lines().count() > 1);
+
+    todo.run(&["--compact", "add", "Stretch"]);
+    let compact = std::fs::read_to_string(&todo.file).unwrap();
+    assert_eq!(compact.lines().count(), 1);
+    assert!(compact.len() < pretty.len() * 2, "{}", compact);
+
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.contains("[0] Email") && listed.contains("[1] Stretch"), "{}", listed);
+}
+
+#[test]
+fn run_with_pins_the_clock_for_repeat_logic() {
+    use clap::Parser;
+
+    let file = std::env::temp_dir().join(format!("todoster-run-with-{}.ron", std::process::id()));
+    let file_arg = file.to_str().unwrap();
+    let run = |args: &[&str], now| {
+        let cli = Cli::try_parse_from([&["todo", "--file", file_arg], args].concat()).unwrap();
+        run_with(cli, now)
+    };
+    let load = || -> TodoList { ron::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap() };
+    let start = Local.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap();
+
+    run(&["add", "Water plants", "--repeat", "2"], start).unwrap();
+    run(&["complete", "0"], start).unwrap();
+    let item = &load().items[0];
+    assert_eq!(item.created_at, Some(start));
+    assert_eq!(item.complete_date, Some(start));
+
+    // A day later the repeat isn't due, so the task is still complete.
+    run(&["add", "Email"], start + Duration::days(1)).unwrap();
+    assert!(load().items[0].complete);
+
+    // Five days on it has reset, having missed one occurrence.
+    let later = start + Duration::days(5);
+    run(&["add", "Stretch"], later).unwrap();
+    let item = &load().items[0];
+    assert!(!item.complete);
+    assert_eq!(item.missed_count, 1);
+
+    run(&["complete", "0"], later).unwrap();
+    assert_eq!(load().items[0].completion_history, vec![start, later]);
+
+    std::fs::remove_file(&file).unwrap();
+    let _ = std::fs::remove_file(SnapshotLog::path_for(&file));
+}
+
+#[test]
+fn cli_inbox_tasks_stay_out_of_list_until_triaged() {
+    let todo = Sandbox::new("inbox");
+
+    todo.run(&["add", "Email"]);
+    todo.run(&["add", "--inbox", "Blog idea"]);
+    let listed = todo.stdout(&["list"]);
+    assert!(!listed.contains("Blog idea"), "{}", listed);
+    assert!(listed.contains("1 task(s) in the inbox"), "{}", listed);
+    assert!(todo.stdout(&["list", "--show-inbox"]).contains("[1] Blog idea"));
+    assert!(todo.stdout(&["inbox"]).contains("[1] Blog idea"));
+
+    assert!(todo.run(&["triage", "1", "--priority", "high", "--tag", "writing"]).status.success());
+    let list = todo.load();
+    assert_eq!(list.items[1].tags, vec!["writing"]);
+    assert_eq!(list.items[1].priority, Some(Priority::High));
+    assert!(todo.stdout(&["list"]).contains("[1] Blog idea"));
+    assert_eq!(todo.stdout(&["inbox"]), "The inbox is empty.\n");
+    assert_eq!(todo.run(&["triage", "0"]).status.code(), Some(4));
+}
+
+#[test]
+fn cli_list_marks_priorities_with_a_legend() {
+    let todo = Sandbox::new("dots");
+
+    todo.run(&["add", "Email"]);
+    assert!(!todo.stdout(&["list"]).contains("Priority:"));
+
+    todo.run(&["add", "Pay rent", "--priority", "high"]);
+    todo.run(&["add", "Bins", "--priority", "low"]);
+    let listed = todo.stdout(&["list"]);
+    assert!(listed.contains("\n· [0] Email\n! [1] Pay rent"), "{}", listed);
+    assert!(listed.contains("\n- [2] Bins"), "{}", listed);
+    assert!(listed.ends_with("Priority: ! high  + medium  - low  · none\n"), "{}", listed);
+    assert!(!todo.stdout(&["list", "--no-legend"]).contains("Priority:"));
+    assert!(todo.stdout(&["--ascii", "list"]).contains(". [0] Email"));
+}
+
+#[test]
+fn auto_archive_moves_old_one_off_tasks_with_the_next_change() {
+    let now = Local.with_ymd_and_hms(2026, 3, 20, 9, 0, 0).unwrap();
+    let mut list = TodoList::default();
+    let tasks = [("Old", None, Some(30)), ("Recent", None, Some(2)), ("Habit", Some(1), Some(30)), ("Open", None, None)];
+    for (text, repeat, done_days_ago) in tasks {
+        let mut item = TodoItem::new(text.to_string(), repeat);
+        if let Some(days) = done_days_ago {
+            item.mark_complete(now - Duration::days(days));
+        }
+        list.items.push(item);
+    }
+    let old = list.take_archivable(now, 14);
+    assert_eq!(old.iter().map(|item| item.text.as_str()).collect::<Vec<_>>(), vec!["Old"]);
+    assert_eq!(list.items.len(), 3);
+
+    let todo = Sandbox::new("auto-archive");
+    todo.write_config("(auto_archive_days: Some(7))");
+    let mut item = TodoItem::new("Filed taxes".to_string(), None);
+    item.mark_complete(Local::now() - Duration::days(10));
+    todo.store(&TodoList {
+        items: vec![item, TodoItem::new("Email".to_string(), None)],
+        ..TodoList::default()
+    });
+
+    // Looking never writes.
+    let output = todo.run(&["list"]);
+    assert!(output.stderr.is_empty());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Filed taxes"));
+    assert!(!TodoList::archive_path_for(&todo.file).exists());
+
+    // Nor does a command that fails.
+    let stored = std::fs::read_to_string(&todo.file).unwrap();
+    assert_eq!(todo.run(&["complete", "999"]).status.code(), Some(4));
+    assert_eq!(std::fs::read_to_string(&todo.file).unwrap(), stored);
+    assert!(!TodoList::archive_path_for(&todo.file).exists());
+
+    let output = todo.run(&["add", "Stretch"]);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Archived 1 task(s)"));
+    assert!(!todo.stdout(&["list"]).contains("Filed taxes"));
+    // The snapshot from before the add still holds it.
+    let log: SnapshotLog = ron::from_str(&std::fs::read_to_string(SnapshotLog::path_for(&todo.file)).unwrap()).unwrap();
+    assert_eq!(log.nth_newest(1).unwrap().list.items[0].text, "Filed taxes");
+    let archive: TodoList = ron::from_str(&std::fs::read_to_string(TodoList::archive_path_for(&todo.file)).unwrap()).unwrap();
+    assert_eq!(archive.items[0].text, "Filed taxes");
+}
+
+#[test]
+fn cli_summary_on_exit_is_opt_in() {
+    let now = Local.with_ymd_and_hms(2026, 1, 14, 10, 0, 0).unwrap();
+    let mut item = TodoItem::new("Read".to_string(), Some(1));
+    item.created_at = Some(now - Duration::days(20));
+    item.completion_history = vec![now - Duration::days(8), now - Duration::days(1), now];
+    let list = TodoList {
+        items: vec![item, TodoItem::new("Undated".to_string(), None)],
+        ..TodoList::default()
+    };
+    assert_eq!(list.activity_since(now - Duration::days(7)), (0, 2));
+
+    let todo = Sandbox::new("summary");
+
+    assert_eq!(todo.stdout(&["add", "Feed gecko"]), "Task added.\n");
+    todo.write_config("(show_summary_on_exit: true)");
+    assert_eq!(todo.stdout(&["add", "Water plants"]), "Task added.\n2 added, 0 completed this week\n");
+    assert_eq!(todo.stdout(&["--quiet", "complete", "0"]), "Task 0 marked complete.\n");
+    assert!(!todo.stdout(&["list"]).contains("this week"));
+}
+
+#[test]
+fn cli_edit_sets_and_clears_the_due_date() {
+    let todo = Sandbox::new("edit-due");
+
+    todo.run(&["add", "Renew passport"]);
+    assert!(todo.run(&["edit", "0", "--due", "2026-05-01"]).status.success());
+    let due = todo.load().items[0].due_date.unwrap();
+    assert_eq!(due.date_naive(), NaiveDate::from_ymd_opt(2026, 5, 1).unwrap());
+
+    // Clearing wins over a new date given alongside it.
+    assert!(todo.run(&["edit", "0", "--clear-due", "--due", "2026-06-01"]).status.success());
+    assert_eq!(todo.load().items[0].due_date, None);
+    assert_eq!(todo.run(&["edit", "0", "--due", "someday"]).status.code(), Some(4));
+}
+
+#[test]
+fn cli_search_count_and_print0_leave_out_everything_else() {
+    let todo = Sandbox::new("search-count");
+
+    for text in ["Feed gecko", "Clean GECKO tank", "Email"] {
+        todo.run(&["add", text]);
+    }
+    assert_eq!(todo.stdout(&["search", "gecko", "--count"]), "2\n");
+    assert_eq!(todo.stdout(&["search", "lizard", "--count"]), "0\n");
+    assert_eq!(todo.stdout(&["search", "gecko", "--print0"]), "[0] [ ] Feed gecko\0[1] [ ] Clean GECKO tank\0");
+    assert_eq!(todo.stdout(&["search", "lizard", "--print0"]), "");
+    assert_eq!(
+        todo.stdout(&["list", "--print0"]),
+        "[0] Feed gecko\0[1] Clean GECKO tank\0[2] Email\0"
+    );
+}
+
+#[test]
+fn cli_list_due_before_shows_an_agenda() {
+    let todo = Sandbox::new("due-before");
+
+    todo.run(&["add", "Pay rent", "--due", "+2d"]);
+    todo.run(&["add", "Renew passport", "--due", "+10d"]);
+    todo.run(&["add", "Someday"]);
+    todo.run(&["add", "Stretch", "--repeat", "1"]);
+    todo.run(&["add", "Water plants", "--repeat", "2"]);
+    todo.run(&["complete", "4"]);
+
+    let listed = todo.stdout(&["list", "--due-before", "+3d"]);
+    assert!(listed.contains("Pay rent") && listed.contains("Stretch"), "{}", listed);
+    // Just done, so there's nothing to do for it yet, even if it's due again soon.
+    assert!(!listed.contains("Water plants"), "{}", listed);
+    assert!(!listed.contains("Renew passport") && !listed.contains("Someday"), "{}", listed);
+    assert!(!todo.stdout(&["list", "--due-before", "today"]).contains("Pay rent"));
+}
+
+#[test]
+fn completed_tasks_are_listed_newest_first_with_undated_last() {
+    let now = Local.with_ymd_and_hms(2026, 3, 20, 9, 0, 0).unwrap();
+    let mut list = TodoList::default();
+    let tasks = [("Old", Some(5)), ("Undated", None), ("Newest", Some(1)), ("Tie A", Some(3)), ("Tie B", Some(3))];
+    for (text, done_days_ago) in tasks {
+        let mut item = TodoItem::new(text.to_string(), None);
+        item.complete = true;
+        item.complete_date = done_days_ago.map(|days| now - Duration::days(days));
+        list.items.push(item);
+    }
+
+    assert_eq!(list.sections(now, false, false).complete, vec![2, 3, 4, 0, 1]);
+    assert_eq!(list.sections(now, false, true).complete, vec![0, 1, 2, 3, 4]);
+}